@@ -1,20 +1,28 @@
-use crate::util;
-
-pub fn part1() -> i64 {
-    util::lines_from_path("data/d1.txt")
-        .map(|x| match x {
-            Ok(line) => mass_to_fuel(line.parse::<i64>().unwrap()),
-            _ => 0,
-        })
-        .sum::<i64>()
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
+
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(1, 1, parse, part1).expecting(3_442_987));
+    registry.add(Part::new(1, 2, parse, part2).expecting(5_161_601));
+}
+
+// One module mass per line
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    input
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| util::parse_number(x.trim()))
+        .collect()
+}
+
+pub fn part1(masses: &[i64]) -> i64 {
+    masses.iter().map(|x| mass_to_fuel(*x)).sum::<i64>()
 }
 
-pub fn part2() -> i64 {
-    util::lines_from_path("data/d1.txt")
-        .map(|x| match x {
-            Ok(line) => mass_to_fuel_inclusive(line.parse::<i64>().unwrap()),
-            _ => 0,
-        })
+pub fn part2(masses: &[i64]) -> i64 {
+    masses
+        .iter()
+        .map(|x| mass_to_fuel_inclusive(*x))
         .sum::<i64>()
 }
 
@@ -1,16 +1,20 @@
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::ParseError;
 use log::trace;
 use std::f32::{self, consts};
 
-pub fn part1() -> i64 {
-    let input = parse(&util::file_as_string("data/d10.txt"));
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(10, 1, parse, part1).expecting(260));
+    registry.add(Part::new(10, 2, parse, part2).expecting(608));
+}
 
+pub fn part1(input: &[Point]) -> i64 {
     // Iterate over each point, and see how many other points are
     // visible to it. Don't count the node itself.
 
     let target = input
         .iter()
-        .map(|x| (num_points_visible_to_asteroid(*x, &input), x))
+        .map(|x| (num_points_visible_to_asteroid(*x, input), x))
         .max_by(|x, y| x.0.cmp(&y.0))
         .unwrap();
 
@@ -19,13 +23,11 @@ pub fn part1() -> i64 {
     target.0 as i64
 }
 
-pub fn part2() -> i64 {
-    let input = parse(&util::file_as_string("data/d10.txt"));
-
+pub fn part2(input: &[Point]) -> i64 {
     // Get this from part 1
     let station = Point { x: 14, y: 17 };
 
-    let mut others = points_visible_to_asteroid(station, &input);
+    let mut others = points_visible_to_asteroid(station, input);
 
     // Will find it on the first pass!
     assert!(others.len() > 200);
@@ -42,12 +44,13 @@ pub fn part2() -> i64 {
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-struct Point {
-    x: i32,
-    y: i32,
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
 }
 
-fn parse(space: &str) -> Vec<Point> {
+// Where the asteroids (#) are on the map
+pub fn parse(space: &str) -> Result<Vec<Point>, ParseError> {
     let mut parsed = Vec::new();
     for (y, line) in space.lines().enumerate() {
         for (x, c) in line.trim().chars().enumerate() {
@@ -57,16 +60,16 @@ fn parse(space: &str) -> Vec<Point> {
                     y: y as i32,
                 }),
                 '.' => continue,
-                x => panic!("Unknown character: {}", x),
+                x => return Err(ParseError::new(format!("unknown character: {:?}", x))),
             };
         }
     }
-    parsed
+    Ok(parsed)
 }
 
 fn circular_distance_from_y_axis(origin: Point, dest: Point) -> f32 {
     let delta_x = (dest.x - origin.x) as f32;
-    let delta_y = -1f32 * ((dest.y - origin.y) as f32);
+    let delta_y = -((dest.y - origin.y) as f32);
 
     // atan2 is a fun function:
     //    pi/2 - pi | 0 - pi/2
@@ -77,7 +80,7 @@ fn circular_distance_from_y_axis(origin: Point, dest: Point) -> f32 {
 
     match delta_y.atan2(delta_x) {
         x if x < 0f32 => (x - consts::FRAC_PI_2).abs(),
-        x if x >= 0f32 && x <= consts::FRAC_PI_2 => consts::FRAC_PI_2 - x,
+        x if (0f32..=consts::FRAC_PI_2).contains(&x) => consts::FRAC_PI_2 - x,
         x if x > consts::FRAC_PI_2 => (2f32 * consts::PI) - (x - consts::FRAC_PI_2),
         _ => panic!("atan2 returned a value I wasn't expecting...."),
     }
@@ -135,7 +138,7 @@ fn points_visible_to_asteroid(asteroid: Point, others: &[Point]) -> Vec<Point> {
                 }
             }
             if can_see {
-                seen.push(destination.clone());
+                seen.push(*destination);
             }
         }
     }
@@ -168,7 +171,7 @@ fn space_parsing_test() {
             Point { x: 3, y: 4 },
             Point { x: 4, y: 4 }
         ],
-        parse(field)
+        parse(field).unwrap()
     );
 }
 
@@ -182,7 +185,8 @@ fn can_see_test() {
 ....#
 ...##"
             .trim(),
-    );
+    )
+    .unwrap();
 
     let can_see = input
         .iter()
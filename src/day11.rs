@@ -1,81 +1,38 @@
-use crate::intcode::{self, HaltStatus, Program};
-use crate::util::{self, DecreasingRange};
+use crate::intcode::io::{run_program_io, IntcodeIo};
+use crate::intcode::Program;
+use crate::solution::{Part, Registry};
+use crate::util::{self, DecreasingRange, ParseError};
 use log::trace;
 use std::collections::HashMap;
 
-pub fn part1() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d11.txt"));
-
-    let mut current_direction = Direction::Up;
-    let mut current_location = Point { x: 0, y: 0 };
-
-    let mut hull = HashMap::new();
-
-    loop {
-        match hull.get(&current_location) {
-            None => intcode::push_input(&mut program, unparse_color(&Color::Black)),
-            Some(color) => intcode::push_input(&mut program, unparse_color(&color)),
-        }
-
-        intcode::run_program(&mut program);
-
-        // get the output, add it to the set
-        let new_color = parse_color(intcode::get_next_output(&mut program).unwrap());
-
-        transition_direction(
-            &mut current_direction,
-            &parse_direction(intcode::get_next_output(&mut program).unwrap()),
-        );
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(11, 1, parse, part1).expecting(2_160));
+    registry.add(Part::new(11, 2, parse, part2).expecting(42));
+}
 
-        hull.insert(current_location, new_color);
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
+}
 
-        transition_point(&mut current_location, &current_direction);
+pub fn part1(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
+    let mut robot = Robot::new();
 
-        match program.halt_status {
-            Some(HaltStatus::Terminated) => break,
-            _ => continue,
-        }
-    }
+    run_program_io(&mut program, &mut robot).unwrap();
 
-    hull.len() as i64
+    robot.hull.len() as i64
 }
 
-pub fn part2() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d11.txt"));
-
-    let mut current_direction = Direction::Up;
-    let mut current_location = Point { x: 0, y: 0 };
-
-    let mut hull = HashMap::new();
+pub fn part2(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
+    let mut robot = Robot::new();
 
     // starting on white now
-    intcode::push_input(&mut program, unparse_color(&Color::White));
-
-    loop {
-        match hull.get(&current_location) {
-            None => intcode::push_input(&mut program, unparse_color(&Color::Black)),
-            Some(color) => intcode::push_input(&mut program, unparse_color(&color)),
-        }
-
-        intcode::run_program(&mut program);
-
-        // get the output, add it to the set
-        let new_color = parse_color(intcode::get_next_output(&mut program).unwrap());
-
-        transition_direction(
-            &mut current_direction,
-            &parse_direction(intcode::get_next_output(&mut program).unwrap()),
-        );
+    robot.hull.insert(robot.location, Color::White);
 
-        hull.insert(current_location, new_color);
+    run_program_io(&mut program, &mut robot).unwrap();
 
-        transition_point(&mut current_location, &current_direction);
-
-        match program.halt_status {
-            Some(HaltStatus::Terminated) => break,
-            _ => continue,
-        }
-    }
+    let hull = robot.hull;
 
     trace!("hull: {:?}", hull);
 
@@ -116,6 +73,47 @@ pub fn part2() -> i64 {
     42
 }
 
+// Shows the program the color of the panel it's on, and does what it
+// says
+struct Robot {
+    hull: HashMap<Point, Color>,
+    location: Point,
+    direction: Direction,
+    // Outputs alternate between a color to paint and a way to turn
+    turning: bool,
+}
+
+impl Robot {
+    fn new() -> Robot {
+        Robot {
+            hull: HashMap::new(),
+            location: Point { x: 0, y: 0 },
+            direction: Direction::Up,
+            turning: false,
+        }
+    }
+}
+
+impl IntcodeIo for Robot {
+    fn read(&mut self) -> Option<i64> {
+        match self.hull.get(&self.location) {
+            None => Some(unparse_color(&Color::Black)),
+            Some(color) => Some(unparse_color(color)),
+        }
+    }
+
+    fn write(&mut self, value: i64) {
+        if self.turning {
+            transition_direction(&mut self.direction, &parse_direction(value));
+            transition_point(&mut self.location, &self.direction);
+        } else {
+            self.hull.insert(self.location, parse_color(value));
+        }
+
+        self.turning = !self.turning;
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 enum Color {
     // 0, every panel starts black
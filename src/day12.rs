@@ -1,32 +1,36 @@
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 //use log::trace;
 use regex::Regex;
 use std::cmp::{self, Ordering};
 use std::collections::HashMap;
 
-pub fn part1() -> i64 {
-    let mut moons = parse("data/d12.txt");
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(12, 1, parse, part1).expecting(9_139));
+    registry.add(Part::new(12, 2, parse, part2).expecting(420_788_524_631_496));
+}
+
+pub fn part1(moons: &[Moon]) -> i64 {
+    let mut moons = moons.to_vec();
 
     for _ in 0..1000 {
         update_velocities(&mut moons);
         update_positions(&mut moons);
     }
 
-    let energy: i32 = moons.iter().map(|x| Moon::energy(x)).sum();
+    let energy: i32 = moons.iter().map(Moon::energy).sum();
 
     energy as i64
 }
 
-pub fn part2() -> i64 {
-    let moons = parse("data/d12.txt");
-
+pub fn part2(moons: &[Moon]) -> i64 {
     let initial_x_state: Vec<(i32, i32)> = moons.iter().map(|m| (m.x, m.x_vel)).collect();
     let initial_y_state: Vec<(i32, i32)> = moons.iter().map(|m| (m.y, m.y_vel)).collect();
     let initial_z_state: Vec<(i32, i32)> = moons.iter().map(|m| (m.z, m.z_vel)).collect();
 
     // get the x period
     let mut x_period = 0;
-    let mut mut_moons = moons.clone();
+    let mut mut_moons = moons.to_vec();
 
     loop {
         update_velocities(&mut mut_moons);
@@ -45,7 +49,7 @@ pub fn part2() -> i64 {
 
     // get the y period
     let mut y_period = 0;
-    mut_moons = moons.clone();
+    mut_moons = moons.to_vec();
 
     loop {
         update_velocities(&mut mut_moons);
@@ -64,7 +68,7 @@ pub fn part2() -> i64 {
 
     // get the z period
     let mut z_period = 0;
-    mut_moons = moons;
+    mut_moons = moons.to_vec();
 
     loop {
         update_velocities(&mut mut_moons);
@@ -121,17 +125,17 @@ fn consolidate_prime_factors(factors: Vec<Vec<u64>>) -> Vec<u64> {
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
-struct Moon {
-    x: i32,
-    y: i32,
-    z: i32,
-    x_vel: i32,
-    y_vel: i32,
-    z_vel: i32,
+pub struct Moon {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub x_vel: i32,
+    pub y_vel: i32,
+    pub z_vel: i32,
 }
 
 impl Moon {
-    fn new(x: i32, y: i32, z: i32) -> Moon {
+    pub fn new(x: i32, y: i32, z: i32) -> Moon {
         Moon {
             x,
             y,
@@ -195,32 +199,34 @@ fn update_positions(moons: &mut Vec<Moon>) {
     }
 }
 
-fn parse(filepath: &str) -> Vec<Moon> {
-    util::lines_from_path(filepath)
-        .filter_map(|x| match x {
-            Ok(x) => Some(parse_line(&x)),
-            _ => None,
-        })
-        .collect::<Vec<Moon>>()
+// One `<x=1, y=2, z=3>` per line
+pub fn parse(input: &str) -> Result<Vec<Moon>, ParseError> {
+    input
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(parse_line)
+        .collect()
 }
 
-fn parse_line(line: &str) -> Moon {
+fn parse_line(line: &str) -> Result<Moon, ParseError> {
     let re = Regex::new(r"<x=(-?\d+), y=(-?\d+), z=(-?\d+)>").unwrap();
 
-    let captures = re.captures(line).unwrap();
+    let captures = re
+        .captures(line)
+        .ok_or_else(|| ParseError::new(format!("not a moon: {:?}", line)))?;
 
-    let x = captures[1].parse::<i32>().unwrap();
-    let y = captures[2].parse::<i32>().unwrap();
-    let z = captures[3].parse::<i32>().unwrap();
+    let x = util::parse_number(&captures[1])?;
+    let y = util::parse_number(&captures[2])?;
+    let z = util::parse_number(&captures[3])?;
 
-    Moon::new(x, y, z)
+    Ok(Moon::new(x, y, z))
 }
 
 #[test]
 fn parsing_test() {
     let _ = env_logger::builder().is_test(true).try_init();
 
-    assert_eq!(Moon::new(1, 2, 3), parse_line("<x=1, y=2, z=3>"));
+    assert_eq!(Moon::new(1, 2, 3), parse_line("<x=1, y=2, z=3>").unwrap());
 
     assert_eq!(
         vec![
@@ -229,7 +235,7 @@ fn parsing_test() {
             Moon::new(1, 17, 16),
             Moon::new(-4, -1, 1)
         ],
-        parse("data/d12.txt")
+        parse(&util::file_as_string("data/d12.txt")).unwrap()
     );
 }
 
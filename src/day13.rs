@@ -1,92 +1,127 @@
+use crate::intcode::io::{run_program_io, IntcodeIo};
 use crate::intcode::{self, Program};
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 //use log::trace;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
 //use std::io::{self, Write};
 
-pub fn part1() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d13.txt"));
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(13, 1, parse, part1).expecting(200));
+    registry.add(Part::new(13, 2, parse, part2).expecting(9_803));
+}
 
-    intcode::run_program(&mut program);
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
+}
 
-    let mut screen = HashMap::new();
-    let mut _score = 0;
-    let mut _ball_x = 0;
-    let mut _paddle_x = 0;
+pub fn part1(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
+    let mut arcade = Arcade::new();
 
-    parse_draw_instructions(
-        &mut screen,
-        &mut program,
-        &mut _score,
-        &mut _ball_x,
-        &mut _paddle_x,
-    );
+    run_program_io(&mut program, &mut arcade).unwrap();
 
-    screen.values().filter(|x| **x == Tile::Block).count() as i64
+    arcade
+        .screen
+        .values()
+        .filter(|x| **x == Tile::Block)
+        .count() as i64
 }
 
-pub fn part2() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d13.txt"));
+pub fn part2(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
+    let mut arcade = Arcade::new();
 
     // Free play!
     intcode::set_state(&mut program, 0, 2);
-    // Get the initial board state
-    intcode::run_program(&mut program);
-
-    let mut score = 0;
-    let mut screen = HashMap::new();
-    let mut last_x = 0;
-    let mut ball_x = 0;
-    let mut paddle_x = 0;
-
-    parse_draw_instructions(
-        &mut screen,
-        &mut program,
-        &mut score,
-        &mut ball_x,
-        &mut paddle_x,
-    );
-
-    // let width = (screen
+
+    // The arcade moves the joystick whenever the game asks, so this
+    // only comes back once the game's over
+    run_program_io(&mut program, &mut arcade).unwrap();
+
+    // let width = (arcade
+    //     .screen
     //     .iter()
     //     .max_by(|x, y| (x.0).0.cmp(&(y.0).0))
     //     .unwrap()
     //     .0)
     //     .0;
-    // let height = (screen
+    // let height = (arcade
+    //     .screen
     //     .iter()
     //     .max_by(|x, y| (x.0).1.cmp(&(y.0).1))
     //     .unwrap()
     //     .0)
     //     .1;
+    //print_screen(&arcade.screen, height, width, arcade.score);
 
-    loop {
-        //print_screen(&screen, height, width, score);
+    arcade.score
+}
 
-        if intcode::is_terminated(&program) {
-            break;
+// The cabinet: draws whatever the program outputs, and plays the
+// joystick whenever it wants input
+struct Arcade {
+    screen: HashMap<(i64, i64), Tile>,
+    score: i64,
+    ball_x: i64,
+    paddle_x: i64,
+    // Where the ball was the last time we moved, to see which way it's
+    // going
+    last_x: i64,
+    // Draw instructions come in threes
+    pending: Vec<i64>,
+}
+
+impl Arcade {
+    fn new() -> Arcade {
+        Arcade {
+            screen: HashMap::new(),
+            score: 0,
+            ball_x: 0,
+            paddle_x: 0,
+            last_x: 0,
+            pending: Vec::new(),
         }
+    }
+}
 
+impl IntcodeIo for Arcade {
+    fn read(&mut self) -> Option<i64> {
         // Game's not over, determine the next move
-        intcode::push_input(&mut program, paddle_direction(last_x, ball_x, paddle_x));
+        let direction = paddle_direction(self.last_x, self.ball_x, self.paddle_x);
 
         // track where it was before we step again
-        last_x = ball_x;
+        self.last_x = self.ball_x;
 
-        intcode::run_program(&mut program);
-
-        parse_draw_instructions(
-            &mut screen,
-            &mut program,
-            &mut score,
-            &mut ball_x,
-            &mut paddle_x,
-        );
+        Some(direction)
     }
 
-    score
+    fn write(&mut self, value: i64) {
+        self.pending.push(value);
+
+        if self.pending.len() < 3 {
+            return;
+        }
+
+        match self.pending[..] {
+            [-1, 0, new_score] => self.score = new_score,
+            [-1, _, _] => panic!("malformed score instruction"),
+            [x_position, y_position, tile_id] => {
+                let tile = parse_tile(tile_id);
+                if tile == Tile::Ball {
+                    self.ball_x = x_position;
+                } else if tile == Tile::HorizontalPaddle {
+                    self.paddle_x = x_position;
+                }
+                self.screen.insert((x_position, y_position), tile);
+            }
+            _ => unreachable!(),
+        }
+
+        self.pending.clear();
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -150,43 +185,6 @@ fn parse_tile(tile_id: i64) -> Tile {
     }
 }
 
-fn parse_draw_instructions(
-    screen: &mut HashMap<(i64, i64), Tile>,
-    program: &mut Program,
-    score: &mut i64,
-    ball_x: &mut i64,
-    paddle_x: &mut i64,
-) {
-    loop {
-        match intcode::get_next_output(program) {
-            Some(-1) => match intcode::get_next_output(program) {
-                Some(0) => match intcode::get_next_output(program) {
-                    Some(new_score) => *score = new_score,
-                    None => panic!("malformed score instruction"),
-                },
-
-                _ => panic!("malformed score instruction"),
-            },
-            Some(x_position) => match intcode::get_next_output(program) {
-                Some(y_position) => match intcode::get_next_output(program) {
-                    Some(tile_id) => {
-                        let tile = parse_tile(tile_id);
-                        if tile == Tile::Ball {
-                            *ball_x = x_position;
-                        } else if tile == Tile::HorizontalPaddle {
-                            *paddle_x = x_position;
-                        }
-                        screen.insert((x_position, y_position), parse_tile(tile_id));
-                    }
-                    None => panic!("Missing tile id for x- and y-coordinate pair"),
-                },
-                None => panic!("missing y coordinate for given x coordinate"),
-            },
-            None => break,
-        }
-    }
-}
-
 // fn get_joystick_input() -> i64 {
 //     print!("move joystick (a|s|d): ");
 //     io::stdout().flush().unwrap();
@@ -1,27 +1,30 @@
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 use regex::Regex;
 use std::collections::{HashMap, VecDeque};
 use std::hash::{Hash, Hasher};
 //use log::trace;
 
-pub fn part1() -> i64 {
-    let mut reactions = HashMap::new();
-
-    for line in util::lines_from_path("data/d14.txt") {
-        match line {
-            Ok(line) => {
-                let reaction = parse_reaction(&line);
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(14, 1, parse, part1).expecting(532_506));
+    registry.add(Part::new(14, 2, parse, part2).expecting(2_595_245));
+}
 
-                reactions.insert(reaction.output, reaction.input);
-            }
-            Err(err) => {
-                println!("got an error parsing a line: {:?}", err);
-                continue;
-            }
-        }
-    }
+// What each chemical is made from, keyed by the chemical (and how
+// much of it one reaction makes)
+pub fn parse(input: &str) -> Result<HashMap<Ingredient, Vec<Ingredient>>, ParseError> {
+    input
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| {
+            let reaction = parse_reaction(x.trim())?;
+            Ok((reaction.output, reaction.input))
+        })
+        .collect()
+}
 
-    ore_for_fuel(&reactions, 1)
+pub fn part1(reactions: &HashMap<Ingredient, Vec<Ingredient>>) -> i64 {
+    ore_for_fuel(reactions, 1)
 }
 
 fn ore_for_fuel(reactions: &HashMap<Ingredient, Vec<Ingredient>>, fuel_quantity: i64) -> i64 {
@@ -80,30 +83,14 @@ fn ore_for_fuel(reactions: &HashMap<Ingredient, Vec<Ingredient>>, fuel_quantity:
     required_ore
 }
 
-pub fn part2() -> i64 {
+pub fn part2(reactions: &HashMap<Ingredient, Vec<Ingredient>>) -> i64 {
     let available_ore: i64 = 1_000_000_000_000;
 
-    let mut reactions = HashMap::new();
-
-    for line in util::lines_from_path("data/d14.txt") {
-        match line {
-            Ok(line) => {
-                let reaction = parse_reaction(&line);
-
-                reactions.insert(reaction.output, reaction.input);
-            }
-            Err(err) => {
-                println!("got an error parsing a line: {:?}", err);
-                continue;
-            }
-        }
-    }
-
-    let ore_per_fuel = ore_for_fuel(&reactions, 1);
+    let ore_per_fuel = ore_for_fuel(reactions, 1);
     let mut target_fuel = available_ore / ore_per_fuel;
 
     loop {
-        let used_ore = ore_for_fuel(&reactions, target_fuel);
+        let used_ore = ore_for_fuel(reactions, target_fuel);
 
         let diff = available_ore - used_ore;
 
@@ -120,9 +107,9 @@ pub fn part2() -> i64 {
 }
 
 #[derive(Debug, Clone)]
-struct Ingredient {
-    name: String,
-    quantity: i64,
+pub struct Ingredient {
+    pub name: String,
+    pub quantity: i64,
 }
 
 // Going to cheat here, so we can lookup by name in a hashmap. Can do
@@ -153,16 +140,19 @@ struct Reaction {
     output: Ingredient,
 }
 
-fn parse_ingredient(raw: &str) -> Ingredient {
+fn parse_ingredient(raw: &str) -> Result<Ingredient, ParseError> {
     let mut splits = raw.split(' ');
 
-    Ingredient {
-        quantity: splits.next().unwrap().parse().unwrap(),
-        name: splits.next().unwrap().to_owned(),
+    match (splits.next(), splits.next()) {
+        (Some(quantity), Some(name)) => Ok(Ingredient {
+            quantity: util::parse_number(quantity)?,
+            name: name.to_owned(),
+        }),
+        _ => Err(ParseError::new(format!("not an ingredient: {:?}", raw))),
     }
 }
 
-fn parse_reaction(reaction: &str) -> Reaction {
+fn parse_reaction(reaction: &str) -> Result<Reaction, ParseError> {
     // String like: "7 A, 1 B => 1 C"
     let re = Regex::new(r"(\d+ [A-Z]+)").unwrap();
 
@@ -171,22 +161,25 @@ fn parse_reaction(reaction: &str) -> Reaction {
     let captures = re.captures_iter(reaction).peekable();
 
     for cap in captures {
-        input.push(parse_ingredient(&cap[0]));
+        input.push(parse_ingredient(&cap[0])?);
     }
 
-    let output = input.pop().unwrap();
+    // The last one is what comes out
+    match input.pop() {
+        Some(output) if !input.is_empty() => Ok(Reaction { input, output }),
+        _ => Err(ParseError::new(format!("not a reaction: {:?}", reaction))),
+    }
+}
 
-    Reaction { input, output }
+#[cfg(test)]
+impl Reaction {
+    fn new(input: Vec<Ingredient>, output: Ingredient) -> Reaction {
+        Reaction { input, output }
+    }
 }
 
 #[test]
 fn parsing_test() {
-    impl Reaction {
-        fn new(input: Vec<Ingredient>, output: Ingredient) -> Reaction {
-            Reaction { input, output }
-        }
-    }
-
     assert_eq!(
         Reaction::new(
             vec![
@@ -196,6 +189,6 @@ fn parsing_test() {
             ],
             Ingredient::new(1, "C".to_owned())
         ),
-        parse_reaction("7 A, 1 B, 3 D => 1 C")
+        parse_reaction("7 A, 1 B, 3 D => 1 C").unwrap()
     );
 }
@@ -1,8 +1,12 @@
-use crate::intcode::{self, Program};
+use crate::intcode::io::{run_program_io, IntcodeIo};
+use crate::intcode::Program;
+use crate::solution::{Part, Registry};
 use crate::util::{
     self,
+    ParseError,
     //DecreasingRange
 };
+use log::trace;
 use rand::{
     distributions::{Distribution, Standard},
     Rng,
@@ -10,8 +14,17 @@ use rand::{
 use std::cmp::{Ordering, Reverse};
 use std::collections::{BinaryHeap, HashMap};
 
-pub fn part1() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d15.txt"));
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(15, 1, parse, part1).expecting(224));
+    registry.add(Part::new(15, 2, parse, part2).expecting(284));
+}
+
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
+}
+
+pub fn part1(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
 
     let mut layout = HashMap::new();
     let mut current_position = (0, 0);
@@ -24,37 +37,29 @@ pub fn part1() -> i64 {
     loop {
         let direction = match directions.pop() {
             None => {
-                println!("Didn't have a direction to move in, so using a random one");
+                trace!("Didn't have a direction to move in, so using a random one");
                 rand::random()
             }
             Some(direction) => direction,
         };
 
-        intcode::push_input(&mut program, direction_to_input(direction));
-        intcode::run_program(&mut program);
-
-        match intcode::get_next_output(&mut program) {
-            None => panic!("Got no output from the program..."),
-            Some(output) => {
-                let new_position = move_in_direction(current_position, direction);
+        let new_position = move_in_direction(current_position, direction);
 
-                match parse_status(output) {
-                    Status::Oxygen => {
-                        layout.insert(current_position, Block::Empty(true));
-                        layout.insert(new_position, Block::Oxygen(true));
-                        break;
-                    }
-                    Status::Wall => {
-                        layout.insert(new_position, Block::Wall);
-                    }
-                    Status::Moved => {
-                        layout.insert(current_position, Block::Empty(true));
-                        layout.insert(new_position, Block::Droid);
-                        current_position = new_position;
-                    }
-                };
+        match move_droid(&mut program, direction) {
+            Status::Oxygen => {
+                layout.insert(current_position, Block::Empty(true));
+                layout.insert(new_position, Block::Oxygen(true));
+                break;
             }
-        }
+            Status::Wall => {
+                layout.insert(new_position, Block::Wall);
+            }
+            Status::Moved => {
+                layout.insert(current_position, Block::Empty(true));
+                layout.insert(new_position, Block::Droid);
+                current_position = new_position;
+            }
+        };
 
         look_around(
             &mut program,
@@ -77,8 +82,8 @@ pub fn part1() -> i64 {
     (directions.len() + 1) as i64
 }
 
-pub fn part2() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d15.txt"));
+pub fn part2(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
 
     let mut layout = HashMap::new();
     let mut current_position = (0, 0);
@@ -95,46 +100,38 @@ pub fn part2() -> i64 {
     loop {
         let direction = match directions.pop() {
             None => {
-                println!("Didn't have a direction to move in, so using a random one");
+                trace!("Didn't have a direction to move in, so using a random one");
                 rand::random()
             }
             Some(direction) => direction,
         };
 
-        intcode::push_input(&mut program, direction_to_input(direction));
-        intcode::run_program(&mut program);
-
-        match intcode::get_next_output(&mut program) {
-            None => panic!("Got no output from the program..."),
-            Some(output) => {
-                let new_position = move_in_direction(current_position, direction);
+        let new_position = move_in_direction(current_position, direction);
 
-                match parse_status(output) {
-                    Status::Oxygen => {
+        match move_droid(&mut program, direction) {
+            Status::Oxygen => {
+                layout.insert(current_position, Block::Empty(true));
+                layout.insert(new_position, Block::Oxygen(true));
+                oxygen_location = new_position;
+                current_position = new_position;
+            }
+            Status::Wall => {
+                if layout.get(&new_position) == Some(Block::Empty(true)).as_ref() {
+                    panic!("Overwrote an explored space with a wall!");
+                }
+                layout.insert(new_position, Block::Wall);
+            }
+            Status::Moved => {
+                match layout.get(&current_position) {
+                    Some(Block::Oxygen(_)) => (), //leave it there
+                    _ => {
                         layout.insert(current_position, Block::Empty(true));
-                        layout.insert(new_position, Block::Oxygen(true));
-                        oxygen_location = new_position;
-                        current_position = new_position;
-                    }
-                    Status::Wall => {
-                        if layout.get(&new_position) == Some(Block::Empty(true)).as_ref() {
-                            panic!("Overwrote an explored space with a wall!");
-                        }
-                        layout.insert(new_position, Block::Wall);
-                    }
-                    Status::Moved => {
-                        match layout.get(&current_position) {
-                            Some(Block::Oxygen(_)) => (), //leave it there
-                            _ => {
-                                layout.insert(current_position, Block::Empty(true));
-                            }
-                        };
-                        layout.insert(new_position, Block::Droid);
-                        current_position = new_position;
                     }
                 };
+                layout.insert(new_position, Block::Droid);
+                current_position = new_position;
             }
-        }
+        };
 
         look_around(
             &mut program,
@@ -218,6 +215,38 @@ fn parse_status(input: i64) -> Status {
     }
 }
 
+// The droid's remote control: one movement command in, one status
+// report back
+struct Remote {
+    command: Option<i64>,
+    status: Option<i64>,
+}
+
+impl IntcodeIo for Remote {
+    fn read(&mut self) -> Option<i64> {
+        self.command.take()
+    }
+
+    fn write(&mut self, value: i64) {
+        self.status = Some(value);
+    }
+}
+
+// Tries to move the droid, and says what happened
+fn move_droid(program: &mut Program, direction: Direction) -> Status {
+    let mut remote = Remote {
+        command: Some(direction_to_input(direction)),
+        status: None,
+    };
+
+    run_program_io(program, &mut remote).unwrap();
+
+    match remote.status {
+        None => panic!("Got no output from the program..."),
+        Some(status) => parse_status(status),
+    }
+}
+
 // fn draw_space(space: &HashMap<(i64, i64), Block>) {
 //     let max_x = space.keys().max_by(|x, y| x.0.cmp(&y.0)).unwrap().0;
 //     let min_x = space.keys().min_by(|x, y| x.0.cmp(&y.0)).unwrap().0;
@@ -316,7 +345,7 @@ fn look_around(
                         if position == current_position {
                             directions.push(direction);
                         } else {
-                            push_path(&layout, directions, current_position, position);
+                            push_path(layout, directions, current_position, position);
                         }
                         break;
                     }
@@ -337,41 +366,30 @@ fn look_in_direction(
         .get(&move_in_direction(current_position, direction))
         .is_none()
     {
-        let moved;
-        intcode::push_input(program, direction_to_input(direction));
-        intcode::run_program(program);
-
-        match intcode::get_next_output(program) {
-            None => panic!("Got no output from the program..."),
-            Some(output) => {
-                let new_position = move_in_direction(current_position, direction);
-
-                match parse_status(output) {
-                    Status::Oxygen => {
-                        let new_block = match layout.get(&new_position) {
-                            Some(Block::Oxygen(explored)) => Block::Oxygen(*explored),
-                            _ => Block::Oxygen(false),
-                        };
-                        layout.insert(new_position, new_block);
-                        moved = true;
-                    }
-                    Status::Wall => {
-                        layout.insert(new_position, Block::Wall);
-                        moved = false;
-                    }
-                    Status::Moved => {
-                        layout.insert(new_position, Block::Empty(false));
-                        moved = true;
-                    }
+        let new_position = move_in_direction(current_position, direction);
+
+        let moved = match move_droid(program, direction) {
+            Status::Oxygen => {
+                let new_block = match layout.get(&new_position) {
+                    Some(Block::Oxygen(explored)) => Block::Oxygen(*explored),
+                    _ => Block::Oxygen(false),
                 };
+                layout.insert(new_position, new_block);
+                true
+            }
+            Status::Wall => {
+                layout.insert(new_position, Block::Wall);
+                false
+            }
+            Status::Moved => {
+                layout.insert(new_position, Block::Empty(false));
+                true
             }
         };
 
         // Need to move back
         if moved {
-            intcode::push_input(program, direction_to_input(opposite_direction(direction)));
-            intcode::run_program(program);
-            intcode::get_next_output(program);
+            move_droid(program, opposite_direction(direction));
         }
     }
 }
@@ -485,7 +503,7 @@ fn push_path(
                         }
                         _ => {
                             match shortest_paths.get(&neighbor.1) {
-                                Some(ref path) if path.directions.len() >= neighbor_path_cost => {
+                                Some(path) if path.directions.len() >= neighbor_path_cost => {
                                     // We already have a path that is at least as
                                     // short, move on to the next
                                     continue;
@@ -530,7 +548,7 @@ fn longest_path(layout: &HashMap<(i64, i64), Block>, current_position: (i64, i64
         match to_explore.pop() {
             None => {
                 // all done
-                break *known_paths.iter().max_by(|x, y| x.1.cmp(&y.1)).unwrap().1;
+                break *known_paths.iter().max_by(|x, y| x.1.cmp(y.1)).unwrap().1;
             }
             Some((position, depth)) => {
                 // check out our neighbors, and see if we've
@@ -1,15 +1,26 @@
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::ParseError;
 
-pub fn part1() -> i64 {
-    let input = digits(&util::file_as_string("data/d16.txt"));
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(16, 1, parse, part1).expecting(27_229_269));
+    registry.add(Part::new(16, 2, parse, part2).expecting(26_857_164));
+}
+
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    match input.trim().chars().find(|x| !x.is_ascii_digit()) {
+        Some(x) => Err(ParseError::new(format!("not a digit: {:?}", x))),
+        None => Ok(digits(input)),
+    }
+}
 
-    let output = fft(input, 100);
+pub fn part1(signal: &[i64]) -> i64 {
+    let output = fft(signal.to_vec(), 100);
 
     vec_to_int(&output[0..8])
 }
 
-pub fn part2() -> i64 {
-    let mut input = digits(&util::file_as_string("data/d16.txt")).repeat(10000);
+pub fn part2(signal: &[i64]) -> i64 {
+    let mut input = signal.repeat(10000);
 
     let offset = vec_to_int(&input[0..7]) as usize;
 
@@ -44,7 +55,7 @@ fn vec_to_int(input: &[i64]) -> i64 {
 fn pattern_iterator<'a>(base_pattern: &'a [i64], repeats: usize) -> impl Iterator<Item = i64> + 'a {
     base_pattern
         .iter()
-        .flat_map(move |x| vec![*x].repeat(repeats))
+        .flat_map(move |x| [*x].repeat(repeats))
         .cycle()
         .skip(1)
 }
@@ -68,7 +79,7 @@ fn fft_step(input: &[i64]) -> Vec<i64> {
     let pattern = vec![0, 1, 0, -1];
 
     (1..input.len() + 1)
-        .map(|x| ith_output(&pattern, &input, x))
+        .map(|x| ith_output(&pattern, input, x))
         .collect()
 }
 
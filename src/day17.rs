@@ -1,12 +1,23 @@
 use crate::intcode::Program;
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 
-pub fn part1() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d17.txt"));
+// Not solved yet, so no answers to check against
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(17, 1, parse, part1));
+    registry.add(Part::new(17, 2, parse, part2));
+}
+
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
+}
+
+pub fn part1(image: &[i64]) -> i64 {
+    let _program = Program::new(image.to_vec());
 
     42
 }
 
-pub fn part2() -> i64 {
+pub fn part2(_image: &[i64]) -> i64 {
     42
 }
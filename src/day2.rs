@@ -1,9 +1,20 @@
 use crate::intcode;
+use crate::intcode::symbolic::{SymbolicProgram, Target};
 use crate::intcode::{ParameterMode, Program};
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 
-pub fn part1() -> i64 {
-    let mut alarm_state = util::comma_separated_to_vec("data/d2.txt");
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(2, 1, parse, part1).expecting(3_306_701));
+    registry.add(Part::new(2, 2, parse, part2).expecting(7_621));
+}
+
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
+}
+
+pub fn part1(image: &[i64]) -> i64 {
+    let mut alarm_state = image.to_vec();
 
     alarm_state[1] = 12;
     alarm_state[2] = 2;
@@ -11,40 +22,25 @@ pub fn part1() -> i64 {
     let mut program = Program::new(alarm_state);
 
     intcode::get_state(
-        intcode::run_program(&mut program),
+        intcode::run_program(&mut program).unwrap(),
         0,
         ParameterMode::Immediate,
     )
+    .unwrap()
 }
 
-pub fn part2() -> i64 {
-    let initial_state = util::comma_separated_to_vec("data/d2.txt");
-
-    let mut program = Program::new(initial_state.to_vec());
-
-    let mut correct_noun = 0;
-    let mut correct_verb = 0;
-
-    'outer: for noun in 0..99 {
-        for verb in 0..99 {
-            intcode::set_state(&mut program, 1, noun);
-            intcode::set_state(&mut program, 2, verb);
-            if intcode::get_state(
-                intcode::run_program(&mut program),
-                0,
-                ParameterMode::Immediate,
-            ) == 19_690_720
-            {
-                correct_noun = noun;
-                correct_verb = verb;
-                break 'outer;
-            } else {
-                program = Program::new(initial_state.to_vec());
-            }
-        }
-    }
-
-    100 * correct_noun + correct_verb
+pub fn part2(image: &[i64]) -> i64 {
+    // Solve for the noun and verb rather than trying them all
+    let mut program = SymbolicProgram::new(image);
+    program.symbolic_cell(1, "noun", 0..=99);
+    program.symbolic_cell(2, "verb", 0..=99);
+
+    let values = program
+        .solve(Target::Memory(0), 19_690_720, 10_000)
+        .unwrap()
+        .unwrap();
+
+    100 * values[0] + values[1]
 }
 
 #[test]
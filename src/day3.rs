@@ -1,3 +1,5 @@
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 
@@ -7,9 +9,21 @@ const ORIGIN: Point = Point {
     traveled: 0,
 };
 
-pub fn part1() -> i64 {
-    let paths = load_paths("data/d3.txt");
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(3, 1, parse, part1).expecting(651));
+    registry.add(Part::new(3, 2, parse, part2).expecting(7_534));
+}
+
+// One wire per line
+pub fn parse(input: &str) -> Result<Vec<Vec<Vector>>, ParseError> {
+    input
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|line| line.trim().split(',').map(parse_path).collect())
+        .collect()
+}
 
+pub fn part1(paths: &[Vec<Vector>]) -> i64 {
     let first_points = points_from_path(ORIGIN, paths[0].to_owned());
     let second_points = points_from_path(ORIGIN, paths[1].to_owned());
 
@@ -17,9 +31,7 @@ pub fn part1() -> i64 {
     distance_between(&ORIGIN, &closest)
 }
 
-pub fn part2() -> i64 {
-    let paths = load_paths("data/d3.txt");
-
+pub fn part2(paths: &[Vec<Vector>]) -> i64 {
     assert_eq!(2, paths.len());
 
     let first_points = points_from_path(ORIGIN, paths[0].to_owned());
@@ -51,7 +63,7 @@ impl Hash for Point {
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
-enum Direction {
+pub enum Direction {
     Right,
     Left,
     Up,
@@ -59,9 +71,9 @@ enum Direction {
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
-struct Vector {
-    direction: Direction,
-    distance: i64,
+pub struct Vector {
+    pub direction: Direction,
+    pub distance: i64,
 }
 
 fn closest_intersection(
@@ -164,32 +176,23 @@ fn points_from_path(start: Point, path: Vec<Vector>) -> HashSet<Point> {
     points
 }
 
-fn parse_path(path: &str) -> Vector {
+fn parse_path(path: &str) -> Result<Vector, ParseError> {
     let mut chars = path.chars();
 
-    let direction = match chars.next().unwrap() {
-        'U' => Direction::Up,
-        'D' => Direction::Down,
-        'R' => Direction::Right,
-        'L' => Direction::Left,
-        x => panic!("unknown direction in data: {}", x),
+    let direction = match chars.next() {
+        Some('U') => Direction::Up,
+        Some('D') => Direction::Down,
+        Some('R') => Direction::Right,
+        Some('L') => Direction::Left,
+        _ => return Err(ParseError::new(format!("unknown direction: {:?}", path))),
     };
 
-    let distance = chars.collect::<String>().parse::<i64>().unwrap();
+    let distance = util::parse_number(chars.as_str())?;
 
-    Vector {
+    Ok(Vector {
         direction,
         distance,
-    }
-}
-
-fn load_paths(filepath: &str) -> Vec<Vec<Vector>> {
-    super::util::lines_from_path(filepath)
-        .filter_map(|x| match x {
-            Ok(line) => Some(line.split(',').map(|x| parse_path(x)).collect()),
-            _ => None,
-        })
-        .collect()
+    })
 }
 
 #[test]
@@ -199,7 +202,7 @@ fn parsing_test() {
             direction: Direction::Up,
             distance: 87
         },
-        parse_path("U87")
+        parse_path("U87").unwrap()
     );
 }
 
@@ -277,16 +280,16 @@ fn part1_test() {
                 ORIGIN,
                 points_from_path(
                     ORIGIN,
-                    vec!("R75", "D30", "R83", "U83", "L12", "D49", "R71", "U7", "L72")
+                    ["R75", "D30", "R83", "U83", "L12", "D49", "R71", "U7", "L72"]
                         .iter()
-                        .map(|x| parse_path(x))
+                        .map(|x| parse_path(x).unwrap())
                         .collect::<Vec<Vector>>()
                 ),
                 points_from_path(
                     ORIGIN,
-                    vec!("U62", "R66", "U55", "R34", "D71", "R55", "D58", "R83")
+                    ["U62", "R66", "U55", "R34", "D71", "R55", "D58", "R83"]
                         .iter()
-                        .map(|x| parse_path(x))
+                        .map(|x| parse_path(x).unwrap())
                         .collect::<Vec<Vector>>()
                 )
             )
@@ -301,18 +304,16 @@ fn part1_test() {
                 ORIGIN,
                 points_from_path(
                     ORIGIN,
-                    vec!(
-                        "R98", "U47", "R26", "D63", "R33", "U87", "L62", "D20", "R33", "U53", "R51"
-                    )
-                    .iter()
-                    .map(|x| parse_path(x))
-                    .collect::<Vec<Vector>>()
+                    ["R98", "U47", "R26", "D63", "R33", "U87", "L62", "D20", "R33", "U53", "R51"]
+                        .iter()
+                        .map(|x| parse_path(x).unwrap())
+                        .collect::<Vec<Vector>>()
                 ),
                 points_from_path(
                     ORIGIN,
-                    vec!("U98", "R91", "D20", "R16", "D67", "R40", "U7", "R15", "U6", "R7")
+                    ["U98", "R91", "D20", "R16", "D67", "R40", "U7", "R15", "U6", "R7"]
                         .iter()
-                        .map(|x| parse_path(x))
+                        .map(|x| parse_path(x).unwrap())
                         .collect::<Vec<Vector>>()
                 )
             )
@@ -339,16 +340,16 @@ fn part2_test() {
         shortest_taxi_cab_path_to_intersection(
             points_from_path(
                 ORIGIN,
-                vec!("R75", "D30", "R83", "U83", "L12", "D49", "R71", "U7", "L72")
+                ["R75", "D30", "R83", "U83", "L12", "D49", "R71", "U7", "L72"]
                     .iter()
-                    .map(|x| parse_path(x))
+                    .map(|x| parse_path(x).unwrap())
                     .collect::<Vec<Vector>>()
             ),
             points_from_path(
                 ORIGIN,
-                vec!("U62", "R66", "U55", "R34", "D71", "R55", "D58", "R83")
+                ["U62", "R66", "U55", "R34", "D71", "R55", "D58", "R83"]
                     .iter()
-                    .map(|x| parse_path(x))
+                    .map(|x| parse_path(x).unwrap())
                     .collect::<Vec<Vector>>()
             )
         )
@@ -359,16 +360,16 @@ fn part2_test() {
         shortest_taxi_cab_path_to_intersection(
             points_from_path(
                 ORIGIN,
-                vec!("R98", "U47", "R26", "D63", "R33", "U87", "L62", "D20", "R33", "U53", "R51")
+                ["R98", "U47", "R26", "D63", "R33", "U87", "L62", "D20", "R33", "U53", "R51"]
                     .iter()
-                    .map(|x| parse_path(x))
+                    .map(|x| parse_path(x).unwrap())
                     .collect::<Vec<Vector>>()
             ),
             points_from_path(
                 ORIGIN,
-                vec!("U98", "R91", "D20", "R16", "D67", "R40", "U7", "R15", "U6", "R7")
+                ["U98", "R91", "D20", "R16", "D67", "R40", "U7", "R15", "U6", "R7"]
                     .iter()
-                    .map(|x| parse_path(x))
+                    .map(|x| parse_path(x).unwrap())
                     .collect::<Vec<Vector>>()
             )
         )
@@ -379,9 +380,9 @@ fn part2_test() {
 fn string_to_points() {
     let points = points_from_path(
         ORIGIN,
-        vec!["U12", "D23", "R13", "L41", "U11"]
+        ["U12", "D23", "R13", "L41", "U11"]
             .iter()
-            .map(|x| parse_path(x))
+            .map(|x| parse_path(x).unwrap())
             .collect::<Vec<Vector>>(),
     );
     // Duplicates....
@@ -408,9 +409,9 @@ fn distance_traveled_test() {
 
     let test_points = points_from_path(
         ORIGIN,
-        vec!["U12", "D23", "R13", "L41", "U11"]
+        ["U12", "D23", "R13", "L41", "U11"]
             .iter()
-            .map(|x| parse_path(x))
+            .map(|x| parse_path(x).unwrap())
             .collect::<Vec<Vector>>(),
     );
 
@@ -427,7 +428,7 @@ fn distance_traveled_test() {
     );
 
     let mut test_points = test_points.into_iter().collect::<Vec<Point>>();
-    test_points.sort_by(|x, y| x.traveled.cmp(&y.traveled));
+    test_points.sort_by_key(|x| x.traveled);
 
     assert!(test_points.len() < 100);
 
@@ -1,12 +1,30 @@
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
+use std::ops::RangeInclusive;
 
-pub fn part1() -> i64 {
-    let lower = 124_075;
-    let upper = 580_769;
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(4, 1, parse, part1).expecting(2_150));
+    registry.add(Part::new(4, 2, parse, part2).expecting(1_462));
+}
+
+// The puzzle input is a range like "124075-580769"
+pub fn parse(input: &str) -> Result<RangeInclusive<i64>, ParseError> {
+    let bounds = input
+        .trim()
+        .split('-')
+        .map(util::parse_number)
+        .collect::<Result<Vec<i64>, ParseError>>()?;
+
+    match bounds[..] {
+        [start, end] => Ok(start..=end),
+        _ => Err(ParseError::new(format!("not a range: {:?}", input.trim()))),
+    }
+}
 
+pub fn part1(range: &RangeInclusive<i64>) -> i64 {
     let mut passwords = 0;
 
-    for i in lower..=upper {
+    for i in range.clone() {
         let input = util::digits(i);
         if doubled_digits(&input) {
             if digits_in_order(&input) {
@@ -22,13 +40,10 @@ pub fn part1() -> i64 {
     passwords
 }
 
-pub fn part2() -> i64 {
-    let lower = 124_075;
-    let upper = 580_769;
-
+pub fn part2(range: &RangeInclusive<i64>) -> i64 {
     let mut passwords = 0;
 
-    for i in lower..=upper {
+    for i in range.clone() {
         let input = util::digits(i);
         if strictly_doubled_digits(&input) {
             if digits_in_order(&input) {
@@ -95,20 +110,12 @@ fn strictly_doubled_digits(input: &[i64]) -> bool {
 fn digits_in_order(input: &[i64]) -> bool {
     input
         .iter()
-        .fold(Some(&-1), |acc, x| match acc {
-            None => None,
-            Some(y) => {
-                if y <= x {
-                    Some(x)
-                } else {
-                    None
-                }
-            }
-        })
+        .try_fold(&-1, |acc, x| if acc <= x { Some(x) } else { None })
         .is_some()
 }
 
 #[test]
 fn part1_test() {
     assert_eq!(vec!(1, 2, 3, 4), util::digits(1234));
+    assert_eq!(124_075..=580_769, parse("124075-580769\n").unwrap());
 }
@@ -1,13 +1,23 @@
 use crate::intcode;
 use crate::intcode::Program;
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 
-pub fn part1() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d5.txt"));
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(5, 1, parse, part1).expecting(9_938_601));
+    registry.add(Part::new(5, 2, parse, part2).expecting(4_283_952));
+}
+
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
+}
+
+pub fn part1(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
 
     intcode::push_input(&mut program, 1);
 
-    let final_state = intcode::run_program(&mut program);
+    let final_state = intcode::run_program(&mut program).unwrap();
 
     match intcode::get_last_output(final_state) {
         Some(x) => x,
@@ -15,12 +25,12 @@ pub fn part1() -> i64 {
     }
 }
 
-pub fn part2() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d5.txt"));
+pub fn part2(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
 
     intcode::push_input(&mut program, 5);
 
-    let final_state = intcode::run_program(&mut program);
+    let final_state = intcode::run_program(&mut program).unwrap();
 
     match intcode::get_next_output(final_state) {
         Some(x) => x,
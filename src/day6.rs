@@ -1,27 +1,32 @@
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::ParseError;
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 
-pub fn part1() -> i64 {
-    let mut orbit_relationships = util::lines_from_path("data/d6.txt")
-        .map(|x| match x {
-            Ok(orbit_string) => parse_orbit(&orbit_string),
-            Err(e) => panic!("Funky line: {:?}", e),
-        })
-        .collect::<HashSet<OrbitRelationship>>();
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(6, 1, parse, part1).expecting(621_125));
+    registry.add(Part::new(6, 2, parse, part2).expecting(550));
+}
+
+// One `A)B` relationship per line
+pub fn parse(input: &str) -> Result<HashSet<OrbitRelationship>, ParseError> {
+    input
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| parse_orbit(x.trim()))
+        .collect()
+}
+
+pub fn part1(orbit_relationships: &HashSet<OrbitRelationship>) -> i64 {
+    let mut orbit_relationships = orbit_relationships.clone();
 
     let orbits = build_orbits(&mut orbit_relationships);
 
     orbit_checksum(orbits)
 }
 
-pub fn part2() -> i64 {
-    let mut orbit_relationships = util::lines_from_path("data/d6.txt")
-        .map(|x| match x {
-            Ok(orbit_string) => parse_orbit(&orbit_string),
-            Err(e) => panic!("Funky line: {:?}", e),
-        })
-        .collect::<HashSet<OrbitRelationship>>();
+pub fn part2(orbit_relationships: &HashSet<OrbitRelationship>) -> i64 {
+    let mut orbit_relationships = orbit_relationships.clone();
 
     let orbits = build_orbits(&mut orbit_relationships);
 
@@ -31,9 +36,9 @@ pub fn part2() -> i64 {
 const COM: &str = "COM";
 
 #[derive(Eq, PartialEq, Debug, Hash, Clone)]
-struct OrbitRelationship {
-    orbited: String,
-    orbitee: String,
+pub struct OrbitRelationship {
+    pub orbited: String,
+    pub orbitee: String,
 }
 
 #[derive(Debug, Clone)]
@@ -92,7 +97,7 @@ fn build_orbits(orbit_relationships: &mut HashSet<OrbitRelationship>) -> HashSet
         }
 
         for o in &to_remove {
-            orbit_relationships.remove(&o);
+            orbit_relationships.remove(o);
         }
         to_remove.clear();
     }
@@ -123,8 +128,8 @@ fn orbital_transfers(orbits: HashSet<Orbit>) -> i64 {
     let you = orbits.get(&Orbit::new("YOU")).unwrap();
     let santa = orbits.get(&Orbit::new("SAN")).unwrap();
 
-    let your_ancestors = ancestors_for_orbit(&orbits, &you);
-    let santa_ancestors = ancestors_for_orbit(&orbits, &santa);
+    let your_ancestors = ancestors_for_orbit(&orbits, you);
+    let santa_ancestors = ancestors_for_orbit(&orbits, santa);
 
     assert!(
         your_ancestors[0] == santa_ancestors[0],
@@ -154,12 +159,15 @@ fn orbital_transfers(orbits: HashSet<Orbit>) -> i64 {
     }
 }
 
-fn parse_orbit(input: &str) -> OrbitRelationship {
+fn parse_orbit(input: &str) -> Result<OrbitRelationship, ParseError> {
     let mut splits = input.split(')');
 
-    OrbitRelationship {
-        orbited: splits.next().unwrap().to_owned(),
-        orbitee: splits.next().unwrap().to_owned(),
+    match (splits.next(), splits.next(), splits.next()) {
+        (Some(orbited), Some(orbitee), None) => Ok(OrbitRelationship {
+            orbited: orbited.to_owned(),
+            orbitee: orbitee.to_owned(),
+        }),
+        _ => Err(ParseError::new(format!("not an orbit: {:?}", input))),
     }
 }
 
@@ -170,7 +178,7 @@ fn test_parsing() {
             orbited: "foo".to_owned(),
             orbitee: "bar".to_owned()
         },
-        parse_orbit("foo)bar")
+        parse_orbit("foo)bar").unwrap()
     );
 }
 
@@ -180,7 +188,7 @@ fn orbit_count() {
         "COM)B", "B)C", "C)D", "D)E", "E)F", "B)G", "G)H", "D)I", "E)J", "J)K", "K)L",
     ]
     .iter()
-    .map(|x| parse_orbit(&x))
+    .map(|x| parse_orbit(x).unwrap())
     .collect();
 
     assert_eq!(42, orbit_checksum(build_orbits(&mut orbit_relationships)));
@@ -205,7 +213,7 @@ fn ancestors_test() {
             "I)SAN",
         ]
         .iter()
-        .map(|x| parse_orbit(&x))
+        .map(|x| parse_orbit(x).unwrap())
         .collect(),
     );
 
@@ -228,7 +236,7 @@ fn orbital_transfers_test() {
         "I)SAN",
     ]
     .iter()
-    .map(|x| parse_orbit(&x))
+    .map(|x| parse_orbit(x).unwrap())
     .collect();
 
     assert_eq!(4, orbital_transfers(build_orbits(&mut orbit_relationships)));
@@ -1,93 +1,37 @@
-use crate::intcode::{get_next_output, push_input, run_program, HaltStatus, Program};
-use crate::util;
+use crate::intcode::amplifiers;
+use crate::intcode::Program;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
+#[cfg(test)]
 use itertools::Itertools;
-use log::trace;
 
-pub fn part1() -> i64 {
-    let program = Program::new(util::comma_separated_to_vec("data/d7.txt"));
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(7, 1, parse, part1).expecting(21_000));
+    registry.add(Part::new(7, 2, parse, part2).expecting(61_379_886));
+}
 
-    (0..5)
-        .permutations(5)
-        .map(|x| run_phase_sequence(&program, x, 0).1)
-        .max()
-        .unwrap()
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
 }
 
-pub fn part2() -> i64 {
-    let program = Program::new(util::comma_separated_to_vec("data/d7.txt"));
+pub fn part1(image: &[i64]) -> i64 {
+    let program = Program::new(image.to_vec());
 
-    (5..10)
-        .permutations(5)
-        .map(|x| run_phase_sequence(&program, x, 0).1)
-        .max()
+    amplifiers::search(&program, 5, &[0, 1, 2, 3, 4], 0)
+        .unwrap()
+        .best()
         .unwrap()
+        .signal
 }
 
-fn run_phase_sequence(program: &Program, sequence: Vec<i64>, input: i64) -> (Program, i64) {
-    let mut amp_a = program.clone();
-    let mut amp_b = program.clone();
-    let mut amp_c = program.clone();
-    let mut amp_d = program.clone();
-    let mut amp_e = program.clone();
-
-    // Add the initial sequence as input
-    push_input(&mut amp_a, sequence[0]);
-    push_input(&mut amp_b, sequence[1]);
-    push_input(&mut amp_c, sequence[2]);
-    push_input(&mut amp_d, sequence[3]);
-    push_input(&mut amp_e, sequence[4]);
-
-    // Insert the initial machine input
-    push_input(&mut amp_a, input);
-
-    // We need to grab our final output
-    let final_output;
-
-    loop {
-        trace!("Using sequence: {:?}", sequence);
-
-        let a_output = get_next_output(run_program(&mut amp_a)).unwrap();
-
-        trace!("amp a output: {}", a_output);
-        trace!("amp a halt status: {:?}", amp_a.halt_status.unwrap());
-
-        push_input(&mut amp_b, a_output);
-
-        let b_output = get_next_output(run_program(&mut amp_b)).unwrap();
+pub fn part2(image: &[i64]) -> i64 {
+    let program = Program::new(image.to_vec());
 
-        trace!("amp b output: {}", b_output);
-        trace!("amp b halt status: {:?}", amp_b.halt_status.unwrap());
-
-        push_input(&mut amp_c, b_output);
-
-        let c_output = get_next_output(run_program(&mut amp_c)).unwrap();
-
-        trace!("amp c output: {}", c_output);
-        trace!("amp c halt status: {:?}", amp_c.halt_status.unwrap());
-
-        push_input(&mut amp_d, c_output);
-
-        let d_output = get_next_output(run_program(&mut amp_d)).unwrap();
-
-        trace!("amp d output: {}", d_output);
-        trace!("amp d halt status: {:?}", amp_d.halt_status.unwrap());
-
-        push_input(&mut amp_e, d_output);
-
-        let e_output = get_next_output(run_program(&mut amp_e)).unwrap();
-
-        trace!("amp e output: {}", e_output);
-        trace!("amp e halt status: {:?}", amp_e.halt_status.unwrap());
-
-        if let Some(HaltStatus::Terminated) = amp_e.halt_status {
-            final_output = e_output;
-            break;
-        } else {
-            push_input(&mut amp_a, e_output);
-        }
-    }
-
-    (amp_e, final_output)
+    amplifiers::search(&program, 5, &[5, 6, 7, 8, 9], 0)
+        .unwrap()
+        .best()
+        .unwrap()
+        .signal
 }
 
 #[test]
@@ -98,21 +42,36 @@ fn phase_sequence() {
         3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
     ]);
 
-    assert_eq!(43210, run_phase_sequence(&input, vec![4, 3, 2, 1, 0], 0).1);
+    assert_eq!(
+        43210,
+        amplifiers::run_circuit(&input, &[4, 3, 2, 1, 0], 0)
+            .unwrap()
+            .unwrap()
+    );
 
     input = Program::new(vec![
         3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23, 99,
         0, 0,
     ]);
 
-    assert_eq!(54321, run_phase_sequence(&input, vec![0, 1, 2, 3, 4], 0).1);
+    assert_eq!(
+        54321,
+        amplifiers::run_circuit(&input, &[0, 1, 2, 3, 4], 0)
+            .unwrap()
+            .unwrap()
+    );
 
     input = Program::new(vec![
         3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33, 1002, 33, 7, 33, 1, 33,
         31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0,
     ]);
 
-    assert_eq!(65210, run_phase_sequence(&input, vec![1, 0, 4, 3, 2], 0).1);
+    assert_eq!(
+        65210,
+        amplifiers::run_circuit(&input, &[1, 0, 4, 3, 2], 0)
+            .unwrap()
+            .unwrap()
+    );
 }
 
 #[test]
@@ -135,7 +94,9 @@ fn feedback_test() {
 
     assert_eq!(
         139_629_729,
-        run_phase_sequence(&input, vec![9, 8, 7, 6, 5], 0).1
+        amplifiers::run_circuit(&input, &[9, 8, 7, 6, 5], 0)
+            .unwrap()
+            .unwrap()
     );
 
     input = Program::new(vec![
@@ -144,5 +105,27 @@ fn feedback_test() {
         1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
     ]);
 
-    assert_eq!(18216, run_phase_sequence(&input, vec![9, 7, 8, 5, 6], 0).1);
+    assert_eq!(
+        18216,
+        amplifiers::run_circuit(&input, &[9, 7, 8, 5, 6], 0)
+            .unwrap()
+            .unwrap()
+    );
+}
+
+#[test]
+fn threaded_test() {
+    let program = Program::new(vec![
+        3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54, -5,
+        54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4, 53,
+        1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+    ]);
+
+    for sequence in (5..10).permutations(5) {
+        let mut network = amplifiers::circuit(&program, &sequence, 0);
+        let mut threaded = network.clone();
+
+        assert_eq!(network.run(), threaded.run_threaded());
+        assert_eq!(network.outputs(4), threaded.outputs(4));
+    }
 }
@@ -1,11 +1,24 @@
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::ParseError;
 use log::trace;
 
-pub fn part1() -> i64 {
-    let input = util::file_as_string("data/d8.txt");
+const WIDTH: usize = 25;
+const HEIGHT: usize = 6;
 
-    let layers = build_layers(&input, 25, 6);
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(8, 1, parse, part1).expecting(1_792));
+    registry.add(Part::new(8, 2, parse, part2).expecting(42));
+}
+
+// The image's layers, each a row-major 25x6 grid of pixels
+pub fn parse(input: &str) -> Result<Vec<Vec<Pixel>>, ParseError> {
+    match input.trim().chars().find(|x| parse_pixel(*x).is_none()) {
+        Some(x) => Err(ParseError::new(format!("unknown pixel: {:?}", x))),
+        None => Ok(build_layers(input, WIDTH, HEIGHT)),
+    }
+}
 
+pub fn part1(layers: &[Vec<Pixel>]) -> i64 {
     let target_layer = layers
         .iter()
         .min_by(|x, y| {
@@ -25,13 +38,9 @@ pub fn part1() -> i64 {
     (layer_ones * layer_twos) as i64
 }
 
-pub fn part2() -> i64 {
-    let input = util::file_as_string("data/d8.txt");
-
-    let width = 25;
-    let height = 6;
-
-    let layers = build_layers(&input, width, height);
+pub fn part2(layers: &[Vec<Pixel>]) -> i64 {
+    let width = WIDTH;
+    let height = HEIGHT;
 
     let mut image = Vec::with_capacity(width * height);
 
@@ -105,7 +114,7 @@ fn build_layers(input: &str, width: usize, height: usize) -> Vec<Vec<Pixel>> {
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum Pixel {
+pub enum Pixel {
     Transparent,
     Black,
     White,
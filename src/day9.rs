@@ -1,13 +1,24 @@
 use crate::intcode;
+use crate::intcode::compiler::Compiled;
 use crate::intcode::Program;
-use crate::util;
+use crate::solution::{Part, Registry};
+use crate::util::{self, ParseError};
 
-pub fn part1() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d9.txt"));
+pub fn register(registry: &mut Registry) {
+    registry.add(Part::new(9, 1, parse, part1).expecting(2_752_191_671));
+    registry.add(Part::new(9, 2, parse, part2).expecting(87_571));
+}
+
+pub fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    util::parse_comma_separated(input)
+}
+
+pub fn part1(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
 
     intcode::push_input(&mut program, 1);
 
-    intcode::run_program(&mut program);
+    intcode::run_program(&mut program).unwrap();
 
     let final_output = intcode::get_next_output(&mut program).unwrap();
 
@@ -16,12 +27,12 @@ pub fn part1() -> i64 {
     final_output
 }
 
-pub fn part2() -> i64 {
-    let mut program = Program::new(util::comma_separated_to_vec("data/d9.txt"));
+pub fn part2(image: &[i64]) -> i64 {
+    let mut program = Program::new(image.to_vec());
 
     intcode::push_input(&mut program, 2);
 
-    intcode::run_program(&mut program);
+    Compiled::new(&program).run(&mut program).unwrap();
 
     let final_output = intcode::get_next_output(&mut program).unwrap();
 
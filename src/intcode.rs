@@ -1,18 +1,59 @@
 use crate::util;
+use arithmetic::Arithmetic;
+use cache::DecodeCache;
+use dialect::{CustomOp, Dialect, Parameter};
+use io::IntcodeIo;
+use limits::Limits;
 use log::trace;
-use std::collections::{HashMap, VecDeque};
+use memory::{FlatMemory, Memory};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+pub mod amplifiers;
+pub mod analysis;
+pub mod arithmetic;
+pub mod ascii;
+pub mod asm;
+pub mod cache;
+pub mod compiler;
+pub mod debugger;
+pub mod dialect;
+pub mod disasm;
+pub mod fuzz;
+pub mod io;
+pub mod journal;
+pub mod limits;
+pub mod memory;
+pub mod network;
+pub mod profiler;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
 
 #[derive(Debug, Clone)]
 pub struct Program {
     pub output: VecDeque<i64>,
-    pub state: HashMap<i64, i64>,
+    // Read through `memory`, written through `set_state`
+    state: Box<dyn Memory>,
     pub pointer: i64,
     pub relative_base: i64,
     pub input: VecDeque<i64>,
     pub halt_status: Option<HaltStatus>,
+    // Kept in sync with `state` by `set_state` and the instructions
+    // themselves, which is why neither is public
+    decoded: DecodeCache,
+    pub limits: Limits,
+    pub arithmetic: Arithmetic,
+    // Instructions run so far
+    pub executed: u64,
+    // None is the standard instruction set. Changed with `set_dialect`,
+    // so the decode cache is cleared along with it.
+    dialect: Option<Arc<Dialect>>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ParameterMode {
     // (1) The Parameter is interpreted as a value
     Immediate,
@@ -27,12 +68,75 @@ pub enum ParameterMode {
 pub enum HaltStatus {
     Terminated, // Got an opcode 99, all done
     WaitingInput,
+    // The program hit an error and stopped. The pointer is left on the
+    // offending instruction, so the caller can patch things up and
+    // run it again.
+    Faulted(IntcodeError),
+    // Ran out of one of the budgets in `limits`. The instruction that
+    // would have gone over hasn't been run.
+    InstructionLimit,
+    MemoryLimit,
+    OutputLimit,
+    DeadlineExceeded,
 }
 
+// Everything that can go wrong while running a program. Addresses
+// refer to the instruction being executed, except for
+// `NegativeAddress`, which carries the bad address itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntcodeError {
+    // The word at `address` doesn't decode to a known opcode
+    UnknownOpcode { address: i64, opcode: i64 },
+    // A parameter mode digit other than 0, 1 or 2
+    InvalidParameterMode { address: i64, mode: i64 },
+    // Tried to read or write below address zero
+    NegativeAddress { address: i64 },
+    // A parameter that is written to was in immediate mode
+    ImmediateWrite { address: i64 },
+    // An arithmetic or address calculation didn't fit in an i64
+    Overflow { address: i64 },
+    // Ran an input instruction with nothing in the input queue
+    MissingInput { address: i64 },
+    // A dialect's DIV or MOD by zero
+    DivideByZero { address: i64 },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { address, opcode } => {
+                write!(f, "unknown opcode {} at address {}", opcode, address)
+            }
+            IntcodeError::InvalidParameterMode { address, mode } => {
+                write!(f, "invalid parameter mode {} at address {}", mode, address)
+            }
+            IntcodeError::NegativeAddress { address } => {
+                write!(f, "tried to access negative address {}", address)
+            }
+            IntcodeError::ImmediateWrite { address } => write!(
+                f,
+                "write parameter in immediate mode at address {}",
+                address
+            ),
+            IntcodeError::Overflow { address } => {
+                write!(f, "arithmetic overflow at address {}", address)
+            }
+            IntcodeError::MissingInput { address } => {
+                write!(f, "no input available at address {}", address)
+            }
+            IntcodeError::DivideByZero { address } => {
+                write!(f, "division by zero at address {}", address)
+            }
+        }
+    }
+}
+
+impl Error for IntcodeError {}
+
 // Parameters that an instruction writes to will never be in immediate
 // mode.
-#[derive(Debug)]
-enum OpCode {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
     // (01) Add parameter 1 and 2, write to parameter 3
     Add(ParameterMode, ParameterMode, ParameterMode),
     // (02) Multiply parameter 1 and 2, write to parameter 3
@@ -59,23 +163,171 @@ enum OpCode {
     AdjustRelBase(ParameterMode),
     // (99)
     Stop,
+    // Anything decoded through a dialect, see `dialect`
+    Custom(CustomOp),
+    // A word the dialect doesn't know, run as a one word no-op (see
+    // `dialect::Unknown::Skip`)
+    Skip,
+}
+
+impl OpCode {
+    // How many parameters follow the opcode itself
+    pub fn arity(&self) -> usize {
+        match self {
+            OpCode::Add(..) | OpCode::Multiply(..) | OpCode::LessThan(..) | OpCode::Equals(..) => 3,
+            OpCode::JumpIfTrue(..) | OpCode::JumpIfFalse(..) => 2,
+            OpCode::Input(_) | OpCode::Output(_) | OpCode::AdjustRelBase(_) => 1,
+            OpCode::Stop | OpCode::Skip => 0,
+            OpCode::Custom(op) => op.arity,
+        }
+    }
+
+    pub fn modes(&self) -> Vec<ParameterMode> {
+        match *self {
+            OpCode::Add(x, y, z)
+            | OpCode::Multiply(x, y, z)
+            | OpCode::LessThan(x, y, z)
+            | OpCode::Equals(x, y, z) => vec![x, y, z],
+            OpCode::JumpIfTrue(x, y) | OpCode::JumpIfFalse(x, y) => vec![x, y],
+            OpCode::Input(x) | OpCode::Output(x) | OpCode::AdjustRelBase(x) => vec![x],
+            OpCode::Stop | OpCode::Skip => vec![],
+            OpCode::Custom(op) => op.modes[..op.arity].to_vec(),
+        }
+    }
+
+    // Which parameters are read, and which are written to
+    pub fn parameters(&self) -> Vec<Parameter> {
+        match *self {
+            OpCode::Add(..) | OpCode::Multiply(..) | OpCode::LessThan(..) | OpCode::Equals(..) => {
+                vec![Parameter::Read, Parameter::Read, Parameter::Write]
+            }
+            OpCode::Input(_) => vec![Parameter::Write],
+            OpCode::Custom(op) => op.parameters[..op.arity].to_vec(),
+            _ => vec![Parameter::Read; self.arity()],
+        }
+    }
+}
+
+// A single cell that an instruction changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub address: i64,
+    pub old: i64,
+    pub new: i64,
+}
+
+// Everything a single instruction did. Instructions that stop or wait
+// for input don't run, so they only fill in `halt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    // Where the instruction lives
+    pub address: i64,
+    pub opcode: OpCode,
+    // Parameter values with their modes applied; write parameters
+    // resolve to the address being written. See `operands()`.
+    operands: [i64; 3],
+    pub write: Option<MemoryWrite>,
+    pub next_pointer: i64,
+    // (before, after), if the instruction moved the relative base
+    pub relative_base: Option<(i64, i64)>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+    pub halt: Option<HaltStatus>,
+}
+
+impl StepResult {
+    fn new(address: i64, opcode: OpCode) -> StepResult {
+        StepResult {
+            address,
+            opcode,
+            operands: [0; 3],
+            write: None,
+            next_pointer: address,
+            relative_base: None,
+            input: None,
+            output: None,
+            halt: None,
+        }
+    }
+
+    pub fn operands(&self) -> &[i64] {
+        if self.halt.is_some() {
+            &[]
+        } else {
+            &self.operands[..self.opcode.arity()]
+        }
+    }
+}
+
+// Hooks called around every instruction. `before_step` sees the
+// program before anything has changed, `after_step` sees it after. A
+// faulting instruction gets `fault` instead of `after_step`, with the
+// pointer still on it.
+pub trait Observer {
+    fn before_step(&mut self, _program: &Program, _address: i64, _opcode: OpCode) {}
+
+    fn after_step(&mut self, _program: &Program, _step: &StepResult) {}
+
+    fn fault(&mut self, _program: &Program, _error: IntcodeError) {}
 }
 
+// Nobody watching
+impl Observer for () {}
+
 impl Program {
     pub fn new(state: Vec<i64>) -> Program {
         Program {
             input: VecDeque::new(),
             output: VecDeque::new(),
-            state: vec_to_map(state),
+            state: Box::new(FlatMemory::from(state)),
             pointer: 0,
             relative_base: 0,
             halt_status: None,
+            decoded: DecodeCache::default(),
+            limits: Limits::default(),
+            arithmetic: Arithmetic::default(),
+            executed: 0,
+            dialect: None,
+        }
+    }
+
+    // Runs through `dialect`'s table instead of the built in set
+    pub fn with_dialect(state: Vec<i64>, dialect: Dialect) -> Program {
+        let mut program = Program::new(state);
+        program.set_dialect(Some(dialect));
+        program
+    }
+
+    pub fn dialect(&self) -> Option<&Dialect> {
+        self.dialect.as_deref()
+    }
+
+    // Anything already decoded was decoded the old way, so it's thrown
+    // away
+    pub fn set_dialect(&mut self, dialect: Option<Dialect>) {
+        self.dialect = dialect.map(Arc::new);
+        self.decoded = DecodeCache::default();
+    }
+
+    pub fn memory(&self) -> &dyn Memory {
+        self.state.as_ref()
+    }
+
+    pub fn decoded(&self) -> &DecodeCache {
+        &self.decoded
+    }
+
+    // For picking a different memory backend, see `memory`
+    pub fn with_memory(state: Box<dyn Memory>) -> Program {
+        Program {
+            state,
+            ..Program::new(vec![])
         }
     }
 }
 
 pub fn push_input(program: &mut Program, input: i64) {
-    program.input.push_back(input);
+    program.input.write(input);
 }
 
 pub fn has_input(program: &Program) -> bool {
@@ -83,41 +335,37 @@ pub fn has_input(program: &Program) -> bool {
 }
 
 pub fn get_next_input(program: &mut Program) -> Option<i64> {
-    program.input.pop_front()
+    program.input.read()
 }
 
 pub fn push_output(program: &mut Program, output: i64) {
-    program.output.push_back(output);
+    program.output.write(output);
 }
 
 pub fn get_next_output(program: &mut Program) -> Option<i64> {
-    program.output.pop_front()
+    program.output.read()
 }
 
 pub fn get_last_output(program: &mut Program) -> Option<i64> {
     program.output.pop_back()
 }
 
-pub fn get_state(program: &Program, pointer: i64, pm: ParameterMode) -> i64 {
+pub fn get_state(program: &Program, pointer: i64, pm: ParameterMode) -> Result<i64, IntcodeError> {
     match pm {
         ParameterMode::Immediate => {
             if pointer < 0 {
-                panic!("Invalid pointer (less than zero): {}", pointer);
+                return Err(IntcodeError::NegativeAddress { address: pointer });
             }
 
-            match program.state.get(&pointer) {
-                Some(x) => {
-                    trace!("getting state, key: {}, value: {}", pointer, x);
-                    *x
-                }
-                None => 0,
-            }
+            let value = program.state.get(pointer);
+            trace!("getting state, key: {}, value: {}", pointer, value);
+            Ok(value)
         }
         ParameterMode::Position => {
             trace!("getting position state, pointer: {}", pointer);
             get_state(
                 program,
-                get_state(program, pointer, ParameterMode::Immediate),
+                get_state(program, pointer, ParameterMode::Immediate)?,
                 ParameterMode::Immediate,
             )
         }
@@ -125,7 +373,10 @@ pub fn get_state(program: &Program, pointer: i64, pm: ParameterMode) -> i64 {
             trace!("getting relative state, pointer: {}", pointer);
             get_state(
                 program,
-                get_state(program, pointer, ParameterMode::Immediate) + program.relative_base,
+                relative_address(
+                    program,
+                    get_state(program, pointer, ParameterMode::Immediate)?,
+                )?,
                 ParameterMode::Immediate,
             )
         }
@@ -133,10 +384,7 @@ pub fn get_state(program: &Program, pointer: i64, pm: ParameterMode) -> i64 {
 }
 
 pub fn is_terminated(program: &Program) -> bool {
-    match program.halt_status {
-        Some(HaltStatus::Terminated) => true,
-        _ => false,
-    }
+    matches!(program.halt_status, Some(HaltStatus::Terminated))
 }
 
 // pub fn is_waiting_input(program: &Program) -> bool {
@@ -146,72 +394,183 @@ pub fn is_terminated(program: &Program) -> bool {
 //     }
 // }
 
-pub fn get_destination(program: &Program, pointer: i64, pm: ParameterMode) -> i64 {
-    match pm {
+pub fn get_destination(
+    program: &Program,
+    pointer: i64,
+    pm: ParameterMode,
+) -> Result<i64, IntcodeError> {
+    let destination = match pm {
         ParameterMode::Immediate => {
-            panic!("Tried to get a write destination using immediate mode!")
-        }
-        ParameterMode::Position => get_state(program, pointer, ParameterMode::Immediate),
-        ParameterMode::Relative => {
-            get_state(program, pointer, ParameterMode::Immediate) + program.relative_base
+            return Err(IntcodeError::ImmediateWrite {
+                address: program.pointer,
+            })
         }
+        ParameterMode::Position => get_state(program, pointer, ParameterMode::Immediate)?,
+        ParameterMode::Relative => relative_address(
+            program,
+            get_state(program, pointer, ParameterMode::Immediate)?,
+        )?,
+    };
+
+    if destination < 0 {
+        return Err(IntcodeError::NegativeAddress {
+            address: destination,
+        });
     }
+
+    Ok(destination)
+}
+
+fn relative_address(program: &Program, offset: i64) -> Result<i64, IntcodeError> {
+    program
+        .relative_base
+        .checked_add(offset)
+        .ok_or(IntcodeError::Overflow {
+            address: program.pointer,
+        })
+}
+
+// Where the instruction's nth word lives, which can run off the end of
+// the address space
+fn parameter_address(program: &Program, n: i64) -> Result<i64, IntcodeError> {
+    program
+        .pointer
+        .checked_add(n)
+        .ok_or(IntcodeError::Overflow {
+            address: program.pointer,
+        })
 }
 
 pub fn set_state(program: &mut Program, key: i64, value: i64) {
-    program.state.insert(key, value);
+    program.state.set(key, value);
+    program.decoded.invalidate(key);
+}
+
+// Runs until the program stops, needs more input, or faults. A fault
+// is both returned and recorded in `halt_status`.
+pub fn run_program(program: &mut Program) -> Result<&mut Program, IntcodeError> {
+    run_program_with(program, &mut ())
 }
 
-pub fn run_program(program: &mut Program) -> &mut Program {
+// Same as `run_program`, but lets an observer look at every
+// instruction as it runs.
+pub fn run_program_with<'a>(
+    program: &'a mut Program,
+    observer: &mut dyn Observer,
+) -> Result<&'a mut Program, IntcodeError> {
     if program.state.is_empty() {
-        return program;
+        return Ok(program);
     }
 
     loop {
-        match parse_opcode(get_state(
-            program,
-            program.pointer,
-            ParameterMode::Immediate,
-        )) {
-            OpCode::Stop => {
-                program.halt_status = Some(HaltStatus::Terminated);
-                break;
+        if step_with(program, observer)?.halt.is_some() {
+            return Ok(program);
+        }
+    }
+}
+
+// Executes exactly one instruction. Stopping or blocking on input is
+// reported through `StepResult::halt` (and `halt_status`), and leaves
+// the pointer where it was.
+pub fn step(program: &mut Program) -> Result<StepResult, IntcodeError> {
+    step_with(program, &mut ())
+}
+
+pub fn step_with(
+    program: &mut Program,
+    observer: &mut dyn Observer,
+) -> Result<StepResult, IntcodeError> {
+    match execute(program, observer) {
+        Ok(step) => {
+            if step.halt.is_none() {
+                program.executed += 1;
             }
-            OpCode::Add(x, y, z) => run_add_instruction(program, x, y, z),
-            OpCode::Multiply(x, y, z) => run_mult_instruction(program, x, y, z),
-            OpCode::Input(x) => {
-                if has_input(&program) {
-                    run_input(program, x);
-                } else {
-                    program.halt_status = Some(HaltStatus::WaitingInput);
-                    break;
-                }
+            program.halt_status = step.halt;
+            observer.after_step(program, &step);
+            Ok(step)
+        }
+        Err(err) => {
+            trace!("program faulted: {}", err);
+            program.halt_status = Some(HaltStatus::Faulted(err));
+            observer.fault(program, err);
+            Err(err)
+        }
+    }
+}
+
+fn execute(program: &mut Program, observer: &mut dyn Observer) -> Result<StepResult, IntcodeError> {
+    let address = program.pointer;
+    let opcode = match program.decoded.get(address) {
+        Some(opcode) => opcode,
+        None => {
+            let opcode = decode(
+                program,
+                address,
+                get_state(program, address, ParameterMode::Immediate)?,
+            )?;
+            program.decoded.insert(address, opcode);
+            opcode
+        }
+    };
+
+    let mut step = StepResult::new(address, opcode);
+
+    if let Some(status) = limits::exceeded(program, opcode) {
+        step.halt = Some(status);
+        return Ok(step);
+    }
+
+    observer.before_step(program, address, opcode);
+
+    match opcode {
+        OpCode::Stop => step.halt = Some(HaltStatus::Terminated),
+        OpCode::Add(x, y, z) => run_add_instruction(program, &mut step, x, y, z)?,
+        OpCode::Multiply(x, y, z) => run_mult_instruction(program, &mut step, x, y, z)?,
+        OpCode::Input(x) => {
+            if has_input(program) {
+                run_input(program, &mut step, x)?;
+            } else {
+                step.halt = Some(HaltStatus::WaitingInput);
             }
-            OpCode::Output(x) => run_output(program, x),
-            OpCode::JumpIfTrue(x, y) => run_jump(program, true, x, y),
-            OpCode::JumpIfFalse(x, y) => run_jump(program, false, x, y),
-            OpCode::LessThan(x, y, z) => run_less_than(program, x, y, z),
-            OpCode::Equals(x, y, z) => run_equals(program, x, y, z),
-            OpCode::AdjustRelBase(x) => run_adjust_relative_base(program, x),
         }
+        OpCode::Output(x) => run_output(program, &mut step, x)?,
+        OpCode::JumpIfTrue(x, y) => run_jump(program, &mut step, true, x, y)?,
+        OpCode::JumpIfFalse(x, y) => run_jump(program, &mut step, false, x, y)?,
+        OpCode::LessThan(x, y, z) => run_less_than(program, &mut step, x, y, z)?,
+        OpCode::Equals(x, y, z) => run_equals(program, &mut step, x, y, z)?,
+        OpCode::AdjustRelBase(x) => run_adjust_relative_base(program, &mut step, x)?,
+        OpCode::Custom(op) => dialect::run(program, &mut step, op)?,
+        OpCode::Skip => program.pointer = parameter_address(program, 1)?,
     }
 
-    program
+    step.next_pointer = program.pointer;
+
+    Ok(step)
+}
+
+// Stores a value and remembers what it replaced
+fn write_state(program: &mut Program, step: &mut StepResult, destination: i64, value: i64) {
+    let old = program.state.set(destination, value);
+    program.decoded.invalidate(destination);
+
+    step.write = Some(MemoryWrite {
+        address: destination,
+        old,
+        new: value,
+    });
 }
 
 fn run_add_instruction(
     program: &mut Program,
+    step: &mut StepResult,
     op1_mode: ParameterMode,
     op2_mode: ParameterMode,
     op3_mode: ParameterMode,
-) {
-    if op3_mode == ParameterMode::Immediate {
-        panic!("Tried to get a write destination using immediate mode!")
-    }
-
-    let operand_1 = get_state(program, program.pointer + 1, op1_mode);
-    let operand_2 = get_state(program, program.pointer + 2, op2_mode);
-    let destination = get_destination(program, program.pointer + 3, op3_mode);
+) -> Result<(), IntcodeError> {
+    let next = parameter_address(program, 4)?;
+    let operand_1 = get_state(program, parameter_address(program, 1)?, op1_mode)?;
+    let operand_2 = get_state(program, parameter_address(program, 2)?, op2_mode)?;
+    let destination = get_destination(program, parameter_address(program, 3)?, op3_mode)?;
 
     trace!(
         "Adding! operand_1: {}, operand_2: {}, destination: {}",
@@ -220,20 +579,32 @@ fn run_add_instruction(
         destination
     );
 
-    program.state.insert(destination, operand_1 + operand_2);
+    let sum = program
+        .arithmetic
+        .add(operand_1, operand_2)
+        .ok_or(IntcodeError::Overflow {
+            address: program.pointer,
+        })?;
+
+    step.operands = [operand_1, operand_2, destination];
+    write_state(program, step, destination, sum);
 
-    program.pointer += 4;
+    program.pointer = next;
+
+    Ok(())
 }
 
 fn run_mult_instruction(
     program: &mut Program,
+    step: &mut StepResult,
     op1_mode: ParameterMode,
     op2_mode: ParameterMode,
     op3_mode: ParameterMode,
-) {
-    let operand_1 = get_state(program, program.pointer + 1, op1_mode);
-    let operand_2 = get_state(program, program.pointer + 2, op2_mode);
-    let destination = get_destination(program, program.pointer + 3, op3_mode);
+) -> Result<(), IntcodeError> {
+    let next = parameter_address(program, 4)?;
+    let operand_1 = get_state(program, parameter_address(program, 1)?, op1_mode)?;
+    let operand_2 = get_state(program, parameter_address(program, 2)?, op2_mode)?;
+    let destination = get_destination(program, parameter_address(program, 3)?, op3_mode)?;
 
     trace!(
         "Multiplying! operand_1: {}, operand_2: {}, destination: {}",
@@ -242,14 +613,32 @@ fn run_mult_instruction(
         destination
     );
 
-    program.state.insert(destination, operand_1 * operand_2);
-    program.pointer += 4;
+    let product =
+        program
+            .arithmetic
+            .multiply(operand_1, operand_2)
+            .ok_or(IntcodeError::Overflow {
+                address: program.pointer,
+            })?;
+
+    step.operands = [operand_1, operand_2, destination];
+    write_state(program, step, destination, product);
+    program.pointer = next;
+
+    Ok(())
 }
 
-fn run_input(program: &mut Program, op_mode: ParameterMode) {
-    let destination = get_destination(program, program.pointer + 1, op_mode);
+fn run_input(
+    program: &mut Program,
+    step: &mut StepResult,
+    op_mode: ParameterMode,
+) -> Result<(), IntcodeError> {
+    let next = parameter_address(program, 2)?;
+    let destination = get_destination(program, parameter_address(program, 1)?, op_mode)?;
 
-    let input = get_next_input(program).unwrap();
+    let input = get_next_input(program).ok_or(IntcodeError::MissingInput {
+        address: program.pointer,
+    })?;
 
     trace!(
         "Taking input! destination: {}, input: {}",
@@ -257,79 +646,116 @@ fn run_input(program: &mut Program, op_mode: ParameterMode) {
         input
     );
 
-    program.state.insert(destination, input);
+    step.operands[0] = destination;
+    step.input = Some(input);
+    write_state(program, step, destination, input);
+
+    program.pointer = next;
 
-    program.pointer += 2;
+    Ok(())
 }
 
-fn run_output(program: &mut Program, op_mode: ParameterMode) {
-    let output = get_state(program, program.pointer + 1, op_mode);
+fn run_output(
+    program: &mut Program,
+    step: &mut StepResult,
+    op_mode: ParameterMode,
+) -> Result<(), IntcodeError> {
+    let next = parameter_address(program, 2)?;
+    let output = get_state(program, parameter_address(program, 1)?, op_mode)?;
 
     trace!("Pushing output: {}", output);
 
+    step.operands[0] = output;
+    step.output = Some(output);
     push_output(program, output);
 
-    program.pointer += 2;
+    program.pointer = next;
+
+    Ok(())
 }
 
 fn run_jump(
     program: &mut Program,
+    step: &mut StepResult,
     jump_if: bool,
     op1_mode: ParameterMode,
     op2_mode: ParameterMode,
-) {
-    let operand_1 = get_state(program, program.pointer + 1, op1_mode);
-    let operand_2 = get_state(program, program.pointer + 2, op2_mode);
+) -> Result<(), IntcodeError> {
+    let operand_1 = get_state(program, parameter_address(program, 1)?, op1_mode)?;
+    let operand_2 = get_state(program, parameter_address(program, 2)?, op2_mode)?;
+
+    step.operands[0] = operand_1;
+    step.operands[1] = operand_2;
 
     match operand_1 {
         0 if !jump_if => program.pointer = operand_2,
         x if jump_if && x != 0 => program.pointer = operand_2,
-        _ => program.pointer += 3,
+        _ => program.pointer = parameter_address(program, 3)?,
     }
+
+    Ok(())
 }
 
 fn run_less_than(
     program: &mut Program,
+    step: &mut StepResult,
     op1_mode: ParameterMode,
     op2_mode: ParameterMode,
     op3_mode: ParameterMode,
-) {
-    let operand_1 = get_state(program, program.pointer + 1, op1_mode);
-    let operand_2 = get_state(program, program.pointer + 2, op2_mode);
-    let destination = get_destination(program, program.pointer + 3, op3_mode);
+) -> Result<(), IntcodeError> {
+    let next = parameter_address(program, 4)?;
+    let operand_1 = get_state(program, parameter_address(program, 1)?, op1_mode)?;
+    let operand_2 = get_state(program, parameter_address(program, 2)?, op2_mode)?;
+    let destination = get_destination(program, parameter_address(program, 3)?, op3_mode)?;
+
+    step.operands = [operand_1, operand_2, destination];
 
     if operand_1 < operand_2 {
-        program.state.insert(destination, 1);
+        write_state(program, step, destination, 1);
     } else {
-        program.state.insert(destination, 0);
+        write_state(program, step, destination, 0);
     }
 
-    program.pointer += 4;
+    program.pointer = next;
+
+    Ok(())
 }
 
 fn run_equals(
     program: &mut Program,
+    step: &mut StepResult,
     op1_mode: ParameterMode,
     op2_mode: ParameterMode,
     op3_mode: ParameterMode,
-) {
-    let operand_1 = get_state(program, program.pointer + 1, op1_mode);
-    let operand_2 = get_state(program, program.pointer + 2, op2_mode);
-    let destination = get_destination(program, program.pointer + 3, op3_mode);
+) -> Result<(), IntcodeError> {
+    let next = parameter_address(program, 4)?;
+    let operand_1 = get_state(program, parameter_address(program, 1)?, op1_mode)?;
+    let operand_2 = get_state(program, parameter_address(program, 2)?, op2_mode)?;
+    let destination = get_destination(program, parameter_address(program, 3)?, op3_mode)?;
+
+    step.operands = [operand_1, operand_2, destination];
 
     if operand_1 == operand_2 {
-        program.state.insert(destination, 1);
+        write_state(program, step, destination, 1);
     } else {
-        program.state.insert(destination, 0);
+        write_state(program, step, destination, 0);
     }
 
-    program.pointer += 4;
+    program.pointer = next;
+
+    Ok(())
 }
 
-fn run_adjust_relative_base(program: &mut Program, op_mode: ParameterMode) {
-    let operand = get_state(program, program.pointer + 1, op_mode);
+fn run_adjust_relative_base(
+    program: &mut Program,
+    step: &mut StepResult,
+    op_mode: ParameterMode,
+) -> Result<(), IntcodeError> {
+    let next = parameter_address(program, 2)?;
+    let operand = get_state(program, parameter_address(program, 1)?, op_mode)?;
+    let old_base = program.relative_base;
 
-    program.relative_base += operand;
+    program.relative_base = relative_address(program, operand)?;
 
     trace!(
         "Adjusting relative base by: {}, new relative base: {}",
@@ -337,7 +763,12 @@ fn run_adjust_relative_base(program: &mut Program, op_mode: ParameterMode) {
         program.relative_base
     );
 
-    program.pointer += 2;
+    step.operands[0] = operand;
+    step.relative_base = Some((old_base, program.relative_base));
+
+    program.pointer = next;
+
+    Ok(())
 }
 
 ////////////////////////////////////////////////////////////////
@@ -345,26 +776,37 @@ fn run_adjust_relative_base(program: &mut Program, op_mode: ParameterMode) {
 
 // Opcodes are 2-digit values, then parameter modes for any parameters
 
-fn parse_opcode(opcode: i64) -> OpCode {
+// Decodes `word` as the program's dialect sees it
+pub fn decode(program: &Program, address: i64, word: i64) -> Result<OpCode, IntcodeError> {
+    match &program.dialect {
+        Some(dialect) => dialect.decode(address, word),
+        None => parse_opcode(address, word),
+    }
+}
+
+fn parse_opcode(address: i64, opcode: i64) -> Result<OpCode, IntcodeError> {
     let mut digits = util::digits(opcode);
     digits.reverse();
 
+    let unknown = IntcodeError::UnknownOpcode { address, opcode };
+    let mode = |digit: i64| parse_mode(address, digit);
+
+    // The opcode is the last two digits, so anything other than 99 has a
+    // zero in the tens place
+    match digits.get(1) {
+        None | Some(0) => (),
+        Some(9) if digits.first() == Some(&9) => (),
+        Some(_) => return Err(unknown),
+    }
+
     let parsed = match digits.first() {
-        None => panic!("Unable to parse opcode!"),
+        None => return Err(unknown),
         Some(1) => match digits.len() {
-            5 => OpCode::Add(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                parse_mode(digits[4]),
-            ),
+            5 => OpCode::Add(mode(digits[2])?, mode(digits[3])?, mode(digits[4])?),
 
-            4 => OpCode::Add(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                ParameterMode::Position,
-            ),
+            4 => OpCode::Add(mode(digits[2])?, mode(digits[3])?, ParameterMode::Position),
             3 => OpCode::Add(
-                parse_mode(digits[2]),
+                mode(digits[2])?,
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
@@ -373,21 +815,13 @@ fn parse_opcode(opcode: i64) -> OpCode {
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(2) => match digits.len() {
-            5 => OpCode::Multiply(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                parse_mode(digits[4]),
-            ),
-            4 => OpCode::Multiply(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                ParameterMode::Position,
-            ),
+            5 => OpCode::Multiply(mode(digits[2])?, mode(digits[3])?, mode(digits[4])?),
+            4 => OpCode::Multiply(mode(digits[2])?, mode(digits[3])?, ParameterMode::Position),
             3 => OpCode::Multiply(
-                parse_mode(digits[2]),
+                mode(digits[2])?,
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
@@ -396,43 +830,35 @@ fn parse_opcode(opcode: i64) -> OpCode {
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(3) => match digits.len() {
-            3 => OpCode::Input(parse_mode(digits[2])),
+            3 => OpCode::Input(mode(digits[2])?),
             2 | 1 => OpCode::Input(ParameterMode::Position),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(4) => match digits.len() {
-            3 => OpCode::Output(parse_mode(digits[2])),
+            3 => OpCode::Output(mode(digits[2])?),
             2 | 1 => OpCode::Output(ParameterMode::Position),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(5) => match digits.len() {
-            4 => OpCode::JumpIfTrue(parse_mode(digits[2]), parse_mode(digits[3])),
-            3 => OpCode::JumpIfTrue(parse_mode(digits[2]), ParameterMode::Position),
+            4 => OpCode::JumpIfTrue(mode(digits[2])?, mode(digits[3])?),
+            3 => OpCode::JumpIfTrue(mode(digits[2])?, ParameterMode::Position),
             2 | 1 => OpCode::JumpIfTrue(ParameterMode::Position, ParameterMode::Position),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(6) => match digits.len() {
-            4 => OpCode::JumpIfFalse(parse_mode(digits[2]), parse_mode(digits[3])),
-            3 => OpCode::JumpIfFalse(parse_mode(digits[2]), ParameterMode::Position),
+            4 => OpCode::JumpIfFalse(mode(digits[2])?, mode(digits[3])?),
+            3 => OpCode::JumpIfFalse(mode(digits[2])?, ParameterMode::Position),
             2 | 1 => OpCode::JumpIfFalse(ParameterMode::Position, ParameterMode::Position),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(7) => match digits.len() {
-            5 => OpCode::LessThan(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                parse_mode(digits[4]),
-            ),
-            4 => OpCode::LessThan(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                ParameterMode::Position,
-            ),
+            5 => OpCode::LessThan(mode(digits[2])?, mode(digits[3])?, mode(digits[4])?),
+            4 => OpCode::LessThan(mode(digits[2])?, mode(digits[3])?, ParameterMode::Position),
             3 => OpCode::LessThan(
-                parse_mode(digits[2]),
+                mode(digits[2])?,
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
@@ -441,21 +867,13 @@ fn parse_opcode(opcode: i64) -> OpCode {
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(8) => match digits.len() {
-            5 => OpCode::Equals(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                parse_mode(digits[4]),
-            ),
-            4 => OpCode::Equals(
-                parse_mode(digits[2]),
-                parse_mode(digits[3]),
-                ParameterMode::Position,
-            ),
+            5 => OpCode::Equals(mode(digits[2])?, mode(digits[3])?, mode(digits[4])?),
+            4 => OpCode::Equals(mode(digits[2])?, mode(digits[3])?, ParameterMode::Position),
             3 => OpCode::Equals(
-                parse_mode(digits[2]),
+                mode(digits[2])?,
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
@@ -464,38 +882,39 @@ fn parse_opcode(opcode: i64) -> OpCode {
                 ParameterMode::Position,
                 ParameterMode::Position,
             ),
-            _ => panic!("Invalid opcode: {}", opcode),
+            _ => return Err(unknown),
         },
         Some(9) => match digits.get(1) {
             Some(9) => OpCode::Stop,
             _ => match digits.len() {
-                3 => OpCode::AdjustRelBase(parse_mode(digits[2])),
+                3 => OpCode::AdjustRelBase(mode(digits[2])?),
                 2 | 1 => OpCode::AdjustRelBase(ParameterMode::Position),
-                _ => panic!("Invalid opcode: {}", opcode),
+                _ => return Err(unknown),
             },
         },
-        Some(_) => panic!("Invalid opcode: {}", opcode),
+        Some(_) => return Err(unknown),
     };
 
     trace!("parsed opcode \"{}\" as: {:?}", opcode, parsed);
 
-    parsed
+    Ok(parsed)
 }
 
-fn parse_mode(mode: i64) -> ParameterMode {
+fn parse_mode(address: i64, mode: i64) -> Result<ParameterMode, IntcodeError> {
     match mode {
-        0 => ParameterMode::Position,
-        1 => ParameterMode::Immediate,
-        2 => ParameterMode::Relative,
-        _ => panic!("Unknown parameter mode: {}", mode),
+        0 => Ok(ParameterMode::Position),
+        1 => Ok(ParameterMode::Immediate),
+        2 => Ok(ParameterMode::Relative),
+        _ => Err(IntcodeError::InvalidParameterMode { address, mode }),
     }
 }
 
-fn vec_to_map(vec: Vec<i64>) -> HashMap<i64, i64> {
-    let mut hash_state = HashMap::new();
+#[cfg(test)]
+fn vec_to_map(vec: Vec<i64>) -> std::collections::HashMap<i64, i64> {
+    let mut hash_state = std::collections::HashMap::new();
 
     for (i, val) in vec.iter().enumerate() {
-        hash_state.insert(i as i64, val.clone());
+        hash_state.insert(i as i64, *val);
     }
     hash_state
 }
@@ -505,19 +924,31 @@ fn day2_intcode_test() {
     let _ = env_logger::builder().is_test(true).try_init();
 
     assert_eq!(
-        run_program(&mut Program::new(vec![1, 0, 0, 0, 99])).state,
+        run_program(&mut Program::new(vec![1, 0, 0, 0, 99]))
+            .unwrap()
+            .state
+            .to_map(),
         vec_to_map(vec!(2, 0, 0, 0, 99))
     );
     assert_eq!(
-        run_program(&mut Program::new(vec![2, 3, 0, 3, 99])).state,
+        run_program(&mut Program::new(vec![2, 3, 0, 3, 99]))
+            .unwrap()
+            .state
+            .to_map(),
         vec_to_map(vec!(2, 3, 0, 6, 99))
     );
     assert_eq!(
-        run_program(&mut Program::new(vec![2, 4, 4, 5, 99, 0])).state,
+        run_program(&mut Program::new(vec![2, 4, 4, 5, 99, 0]))
+            .unwrap()
+            .state
+            .to_map(),
         vec_to_map(vec!(2, 4, 4, 5, 99, 9801))
     );
     assert_eq!(
-        run_program(&mut Program::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99])).state,
+        run_program(&mut Program::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]))
+            .unwrap()
+            .state
+            .to_map(),
         vec_to_map(vec!(30, 1, 1, 4, 2, 5, 6, 0, 99))
     );
 }
@@ -531,12 +962,18 @@ fn basic_io_test() {
 
     trace!("Program: {:?}", input);
 
-    assert_eq!(1, get_next_output(run_program(&mut input)).unwrap());
+    assert_eq!(
+        1,
+        get_next_output(run_program(&mut input).unwrap()).unwrap()
+    );
 
     input = Program::new(vec![3, 0, 4, 0, 99]);
     push_input(&mut input, 42);
 
-    assert_eq!(42, get_next_output(run_program(&mut input)).unwrap());
+    assert_eq!(
+        42,
+        get_next_output(run_program(&mut input).unwrap()).unwrap()
+    );
 }
 
 #[test]
@@ -546,19 +983,21 @@ fn opcode_mode_test() {
     assert_eq!(
         1101,
         get_state(
-            run_program(&mut Program::new(vec![1101, 100, -1, 4, 0])),
+            run_program(&mut Program::new(vec![1101, 100, -1, 4, 0])).unwrap(),
             0,
             ParameterMode::Immediate
         )
+        .unwrap()
     );
 
     assert_eq!(
         99,
         get_state(
-            run_program(&mut Program::new(vec![1002, 4, 3, 4, 33])),
+            run_program(&mut Program::new(vec![1002, 4, 3, 4, 33])).unwrap(),
             4,
             ParameterMode::Immediate
         )
+        .unwrap()
     )
 }
 
@@ -569,50 +1008,50 @@ fn four_more_opcodes() {
     // equals
     let mut input = Program::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
     push_input(&mut input, 8);
-    assert_eq!(1, run_program(&mut input).output[0]);
+    assert_eq!(1, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
     push_input(&mut input, 42);
-    assert_eq!(0, run_program(&mut input).output[0]);
+    assert_eq!(0, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![3, 3, 1108, -1, 8, 3, 4, 3, 99]);
     push_input(&mut input, 42);
-    assert_eq!(0, run_program(&mut input).output[0]);
+    assert_eq!(0, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![3, 3, 1108, -1, 8, 3, 4, 3, 99]);
     push_input(&mut input, 8);
-    assert_eq!(1, run_program(&mut input).output[0]);
+    assert_eq!(1, run_program(&mut input).unwrap().output[0]);
 
     // less than
     input = Program::new(vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8]);
     push_input(&mut input, 42);
-    assert_eq!(0, run_program(&mut input).output[0]);
+    assert_eq!(0, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8]);
     push_input(&mut input, 2);
-    assert_eq!(1, run_program(&mut input).output[0]);
+    assert_eq!(1, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![3, 3, 1107, -1, 8, 3, 4, 3, 99]);
     push_input(&mut input, 42);
-    assert_eq!(0, run_program(&mut input).output[0]);
+    assert_eq!(0, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![3, 3, 1107, -1, 8, 3, 4, 3, 99]);
     push_input(&mut input, 4);
-    assert_eq!(1, run_program(&mut input).output[0]);
+    assert_eq!(1, run_program(&mut input).unwrap().output[0]);
 
     // Jumps
     input = Program::new(vec![
         3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
     ]);
     push_input(&mut input, 42);
-    assert_eq!(1, run_program(&mut input).output[0]);
+    assert_eq!(1, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![
         3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
     ]);
     push_input(&mut input, 0);
 
-    assert_eq!(0, run_program(&mut input).output[0]);
+    assert_eq!(0, run_program(&mut input).unwrap().output[0]);
 
     // More complex test
     input = Program::new(vec![
@@ -621,7 +1060,7 @@ fn four_more_opcodes() {
         1105, 1, 46, 98, 99,
     ]);
     push_input(&mut input, 4);
-    assert_eq!(999, run_program(&mut input).output[0]);
+    assert_eq!(999, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![
         3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
@@ -629,7 +1068,7 @@ fn four_more_opcodes() {
         1105, 1, 46, 98, 99,
     ]);
     push_input(&mut input, 8);
-    assert_eq!(1000, run_program(&mut input).output[0]);
+    assert_eq!(1000, run_program(&mut input).unwrap().output[0]);
 
     input = Program::new(vec![
         3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
@@ -637,7 +1076,7 @@ fn four_more_opcodes() {
         1105, 1, 46, 98, 99,
     ]);
     push_input(&mut input, 42);
-    assert_eq!(1001, run_program(&mut input).output[0]);
+    assert_eq!(1001, run_program(&mut input).unwrap().output[0]);
 }
 
 #[test]
@@ -648,7 +1087,7 @@ fn relative_test() {
         109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
     ]);
 
-    run_program(&mut program);
+    run_program(&mut program).unwrap();
 
     for (expected, produced) in vec![
         109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
@@ -664,23 +1103,27 @@ fn relative_test() {
 fn large_numbers_test() {
     let _ = env_logger::builder().is_test(true).try_init();
 
-    let mut program = Program::new(vec![1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0]);
+    for arithmetic in [Arithmetic::Checked, Arithmetic::Wrapping] {
+        let mut program = Program::new(vec![1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0]);
+        program.arithmetic = arithmetic;
 
-    run_program(&mut program);
+        run_program(&mut program).unwrap();
 
-    assert_eq!(
-        16,
-        util::digits(get_next_output(&mut program).unwrap()).len()
-    );
+        assert_eq!(
+            16,
+            util::digits(get_next_output(&mut program).unwrap()).len()
+        );
 
-    program = Program::new(vec![104, 1_125_899_906_842_624, 99]);
+        program = Program::new(vec![104, 1_125_899_906_842_624, 99]);
+        program.arithmetic = arithmetic;
 
-    run_program(&mut program);
+        run_program(&mut program).unwrap();
 
-    assert_eq!(
-        1_125_899_906_842_624,
-        get_next_output(&mut program).unwrap()
-    );
+        assert_eq!(
+            1_125_899_906_842_624,
+            get_next_output(&mut program).unwrap()
+        );
+    }
 }
 
 #[test]
@@ -699,3 +1142,166 @@ fn output_queue_test() {
 
     assert!(get_next_output(&mut program).is_none());
 }
+
+#[test]
+fn fault_test() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let mut program = Program::new(vec![1101, 1, 1, 5, 42, 0]);
+    let err = IntcodeError::UnknownOpcode {
+        address: 4,
+        opcode: 42,
+    };
+
+    assert_eq!(Err(err), run_program(&mut program).map(|_| ()));
+    assert_eq!(Some(HaltStatus::Faulted(err)), program.halt_status);
+    assert_eq!(4, program.pointer);
+
+    // Patch the bad word and pick up where we left off
+    set_state(&mut program, 4, 99);
+    run_program(&mut program).unwrap();
+    assert!(is_terminated(&program));
+    assert_eq!(2, get_state(&program, 5, ParameterMode::Immediate).unwrap());
+
+    assert_eq!(
+        Err(IntcodeError::InvalidParameterMode {
+            address: 0,
+            mode: 3
+        }),
+        run_program(&mut Program::new(vec![301, 0, 0, 0, 99])).map(|_| ())
+    );
+
+    assert_eq!(
+        Err(IntcodeError::ImmediateWrite { address: 0 }),
+        run_program(&mut Program::new(vec![11101, 1, 1, 0, 99])).map(|_| ())
+    );
+
+    assert_eq!(
+        Err(IntcodeError::NegativeAddress { address: -1 }),
+        run_program(&mut Program::new(vec![4, -1, 99])).map(|_| ())
+    );
+
+    assert_eq!(
+        Err(IntcodeError::Overflow { address: 0 }),
+        run_program(&mut Program::new(vec![1102, i64::MAX, 2, 0, 99])).map(|_| ())
+    );
+
+    // An output at the very end of memory, with nowhere to go after it
+    let mut program = Program::new(vec![1101, 0, 104, i64::MAX - 1, 1105, 1, i64::MAX - 1]);
+    assert_eq!(
+        Err(IntcodeError::Overflow {
+            address: i64::MAX - 1
+        }),
+        run_program(&mut program).map(|_| ())
+    );
+    assert_eq!(None, get_next_output(&mut program));
+}
+
+#[test]
+fn step_test() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let mut program = Program::new(vec![3, 9, 1001, 9, 5, 9, 4, 9, 99, 0]);
+    push_input(&mut program, 37);
+
+    let input = step(&mut program).unwrap();
+    assert_eq!(OpCode::Input(ParameterMode::Position), input.opcode);
+    assert_eq!(&[9], input.operands());
+    assert_eq!(Some(37), input.input);
+    assert_eq!(
+        Some(MemoryWrite {
+            address: 9,
+            old: 0,
+            new: 37
+        }),
+        input.write
+    );
+    assert_eq!((0, 2), (input.address, input.next_pointer));
+
+    let add = step(&mut program).unwrap();
+    assert_eq!(&[37, 5, 9], add.operands());
+    assert_eq!(42, add.write.unwrap().new);
+    assert_eq!(6, program.pointer);
+
+    assert_eq!(Some(42), step(&mut program).unwrap().output);
+
+    let stop = step(&mut program).unwrap();
+    assert_eq!(Some(HaltStatus::Terminated), stop.halt);
+    assert!(stop.operands().is_empty());
+    assert_eq!(8, program.pointer);
+}
+
+#[test]
+fn observer_test() {
+    #[derive(Default)]
+    struct Counter {
+        before: Vec<i64>,
+        after: Vec<i64>,
+    }
+
+    impl Observer for Counter {
+        fn before_step(&mut self, program: &Program, address: i64, _opcode: OpCode) {
+            assert_eq!(program.pointer, address);
+            self.before.push(address);
+        }
+
+        fn after_step(&mut self, program: &Program, step: &StepResult) {
+            assert_eq!(program.pointer, step.next_pointer);
+            self.after.push(step.address);
+        }
+    }
+
+    let mut counter = Counter::default();
+    let mut program = Program::new(vec![109, 5, 203, 0, 99, 0]);
+
+    run_program_with(&mut program, &mut counter).unwrap();
+    assert_eq!(Some(HaltStatus::WaitingInput), program.halt_status);
+
+    push_input(&mut program, 7);
+    run_program_with(&mut program, &mut counter).unwrap();
+
+    assert_eq!(vec![0, 2, 2, 4], counter.before);
+    assert_eq!(counter.before, counter.after);
+    assert_eq!(7, get_state(&program, 5, ParameterMode::Immediate).unwrap());
+}
+
+#[test]
+fn self_modifying_test() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The second time around, the first instruction has been rewritten
+    // to read address 100 instead of outputting 100 directly
+    let mut program = Program::new(
+        asm::assemble(
+            "
+top:    OUT #100
+        JNZ [count], #end
+        ADD #1, #0, [count]
+        ADD #4, #0, [top]
+        JZ #0, #top
+end:    HALT
+count:  .data 0",
+        )
+        .unwrap(),
+    );
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(
+        vec![100, 0],
+        program.output.into_iter().collect::<Vec<i64>>()
+    );
+
+    // Patching from the outside has to be noticed too
+    let mut program = Program::new(vec![104, 100, 99]);
+
+    step(&mut program).unwrap();
+    set_state(&mut program, 0, 4);
+    program.pointer = 0;
+    step(&mut program).unwrap();
+
+    assert_eq!(
+        vec![100, 0],
+        program.output.into_iter().collect::<Vec<i64>>()
+    );
+}
@@ -0,0 +1,193 @@
+// Day 7 style amplifier circuits: one copy of a program per stage,
+// each given a phase setting as its first input, wired in a feedback
+// loop. Searching for the best phase settings tries every permutation,
+// spread over a pool of threads.
+
+use super::network::Network;
+use super::{IntcodeError, Program};
+use itertools::Itertools;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub sequence: Vec<i64>,
+    // The last thing the last amplifier output
+    pub signal: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ranking {
+    // Strongest signal first. Ties are broken by sequence, so the
+    // order doesn't depend on which thread finished first.
+    pub ranked: Vec<Candidate>,
+}
+
+impl Ranking {
+    pub fn best(&self) -> Option<&Candidate> {
+        self.ranked.first()
+    }
+}
+
+// One amplifier per phase setting, in a feedback loop, with `input`
+// going into the first one. No phases is an empty network.
+pub fn circuit(program: &Program, sequence: &[i64], input: i64) -> Network {
+    let mut network = Network::ring(vec![program.clone(); sequence.len()]);
+
+    for (i, phase) in sequence.iter().enumerate() {
+        network.push_input(i, *phase);
+    }
+    if !sequence.is_empty() {
+        network.push_input(0, input);
+    }
+
+    network
+}
+
+// Runs the circuit, returning the final signal, if there was one. With
+// no amplifiers there's nothing to produce one.
+pub fn run_circuit(
+    program: &Program,
+    sequence: &[i64],
+    input: i64,
+) -> Result<Option<i64>, IntcodeError> {
+    let last = match sequence.len().checked_sub(1) {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let mut network = circuit(program, sequence, input);
+    network.run()?;
+
+    Ok(network.outputs(last).last().copied())
+}
+
+// Tries every way of picking `amplifiers` different phases from
+// `phases`, using as many threads as there are cores
+pub fn search(
+    program: &Program,
+    amplifiers: usize,
+    phases: &[i64],
+    input: i64,
+) -> Result<Ranking, IntcodeError> {
+    let threads = thread::available_parallelism().map_or(1, |x| x.get());
+
+    search_with_threads(program, amplifiers, phases, input, threads)
+}
+
+// A sequence that never produces a signal is left out of the ranking.
+// If any of them fault, the error from the first one (in permutation
+// order) is returned.
+pub fn search_with_threads(
+    program: &Program,
+    amplifiers: usize,
+    phases: &[i64],
+    input: i64,
+    threads: usize,
+) -> Result<Ranking, IntcodeError> {
+    let sequences = phases
+        .iter()
+        .copied()
+        .permutations(amplifiers)
+        .collect::<Vec<Vec<i64>>>();
+    let next = AtomicUsize::new(0);
+
+    let mut results = thread::scope(|scope| {
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                // Programs can't be shared between threads, only sent
+                let program = program.clone();
+                let (next, sequences) = (&next, &sequences);
+
+                scope.spawn(move || {
+                    let mut results = Vec::new();
+
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+
+                        match sequences.get(i) {
+                            Some(sequence) => {
+                                results.push((i, run_circuit(&program, sequence, input)))
+                            }
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|x| x.join().expect("search thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_by_key(|x| x.0);
+
+    let mut ranked = Vec::new();
+
+    for (i, result) in results {
+        if let Some(signal) = result? {
+            ranked.push(Candidate {
+                sequence: sequences[i].clone(),
+                signal,
+            });
+        }
+    }
+
+    ranked.sort_by(|a, b| b.signal.cmp(&a.signal).then(a.sequence.cmp(&b.sequence)));
+
+    Ok(Ranking { ranked })
+}
+
+#[test]
+fn search_test() {
+    let program = Program::new(vec![
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ]);
+
+    let ranking = search(&program, 5, &[5, 6, 7, 8, 9], 0).unwrap();
+
+    assert_eq!(
+        Some(&Candidate {
+            sequence: vec![9, 8, 7, 6, 5],
+            signal: 139_629_729
+        }),
+        ranking.best()
+    );
+    assert_eq!(120, ranking.ranked.len());
+    assert!(ranking
+        .ranked
+        .windows(2)
+        .all(|x| x[0].signal >= x[1].signal));
+
+    // Same answer however many threads there are
+    assert_eq!(
+        ranking,
+        search_with_threads(&program, 5, &[5, 6, 7, 8, 9], 0, 1).unwrap()
+    );
+}
+
+#[test]
+fn more_stages_test() {
+    // Adds its phase to the signal
+    let program = Program::new(vec![3, 11, 3, 12, 1, 11, 12, 11, 4, 11, 99, 0, 0]);
+
+    // Six stages picked from eight phases: the best is the six biggest,
+    // in whatever order sorts first
+    let ranking = search(&program, 6, &(0..8).collect::<Vec<i64>>(), 1).unwrap();
+
+    assert_eq!(
+        Some(&Candidate {
+            sequence: vec![2, 3, 4, 5, 6, 7],
+            signal: 28
+        }),
+        ranking.best()
+    );
+    assert_eq!(20_160, ranking.ranked.len());
+
+    // No stages at all never gives a signal
+    assert_eq!(Ok(None), run_circuit(&program, &[], 1));
+    assert_eq!(None, search(&program, 0, &[1, 2], 1).unwrap().best());
+}
@@ -0,0 +1,489 @@
+// Static analysis of a program image, without running it. Builds on the
+// disassembler: the code it finds is split into basic blocks, joined up
+// by the jumps between them, and then we look for a couple of things
+// that are easy to miss when reading a listing:
+//
+// - instructions that write into code (self-modifying programs)
+// - subroutines, spotted by the way compiled Intcode calls them: store
+//   a return address through the relative base, then jump
+//   unconditionally, and come back with a jump through the relative
+//   base
+//
+// Only position mode addresses can be worked out statically, so reads
+// and writes through the relative base are left out of everything.
+
+use super::disasm::{self, Entry};
+use super::{OpCode, Parameter, ParameterMode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: i64,
+    // One past the last word of the last instruction
+    pub end: i64,
+    pub instructions: Vec<Entry>,
+    // Position mode addresses read and written by the block
+    pub reads: BTreeSet<i64>,
+    pub writes: BTreeSet<i64>,
+    // Ends in a jump whose target we can't work out
+    pub indirect: bool,
+    // Ends in an unconditional jump through the relative base
+    pub returns: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    // Falling through to the next instruction
+    Next,
+    Jump,
+    Call,
+    // From a call to where the subroutine comes back to
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub from: i64,
+    pub to: i64,
+    pub kind: EdgeKind,
+}
+
+// The instruction at `address` writes to `target`, which is part of the
+// instruction at `instruction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModification {
+    pub address: i64,
+    pub target: i64,
+    pub instruction: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub entry: i64,
+    // Blocks that call it
+    pub callers: Vec<i64>,
+    // Blocks reachable from the entry without following calls
+    pub blocks: BTreeSet<i64>,
+    // Which of those return
+    pub returns: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub blocks: BTreeMap<i64, Block>,
+    pub edges: Vec<Edge>,
+    pub self_modifications: Vec<SelfModification>,
+    pub subroutines: BTreeMap<i64, Subroutine>,
+}
+
+impl Analysis {
+    pub fn new(image: &[i64]) -> Analysis {
+        let instructions = disasm::disassemble(image)
+            .into_iter()
+            .filter(|x| matches!(x, Entry::Instruction { .. }))
+            .collect::<Vec<Entry>>();
+
+        let blocks = split_blocks(instructions);
+        let mut edges = Vec::new();
+
+        for block in blocks.values() {
+            edges.extend(block_edges(block, &blocks));
+        }
+        edges.sort();
+        edges.dedup();
+
+        let mut analysis = Analysis {
+            self_modifications: self_modifications(&blocks),
+            blocks,
+            edges,
+            subroutines: BTreeMap::new(),
+        };
+        analysis.subroutines = analysis.find_subroutines();
+
+        analysis
+    }
+
+    pub fn block_containing(&self, address: i64) -> Option<&Block> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|x| x.1)
+            .filter(|x| address < x.end)
+    }
+
+    pub fn edges_from(&self, block: i64) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |x| x.from == block)
+    }
+
+    fn find_subroutines(&self) -> BTreeMap<i64, Subroutine> {
+        let mut subroutines = BTreeMap::new();
+
+        for edge in self.edges.iter().filter(|x| x.kind == EdgeKind::Call) {
+            subroutines
+                .entry(edge.to)
+                .or_insert_with(|| self.subroutine(edge.to))
+                .callers
+                .push(edge.from);
+        }
+
+        subroutines
+    }
+
+    fn subroutine(&self, entry: i64) -> Subroutine {
+        let mut blocks = BTreeSet::new();
+        let mut to_visit = vec![entry];
+
+        while let Some(block) = to_visit.pop() {
+            if !blocks.insert(block) {
+                continue;
+            }
+
+            // A call carries on from where the callee comes back to
+            to_visit.extend(
+                self.edges_from(block)
+                    .filter(|x| x.kind != EdgeKind::Call)
+                    .map(|x| x.to),
+            );
+        }
+
+        let returns = blocks
+            .iter()
+            .filter(|x| self.blocks[x].returns)
+            .copied()
+            .collect();
+
+        Subroutine {
+            entry,
+            callers: Vec::new(),
+            blocks,
+            returns,
+        }
+    }
+
+    // Graphviz. Calls are dashed, and the way back from them dotted.
+    // Blocks something writes into are red, and blocks that return have
+    // a double border.
+    pub fn to_dot(&self) -> String {
+        let modified = self
+            .self_modifications
+            .iter()
+            .filter_map(|x| self.block_containing(x.target))
+            .map(|x| x.start)
+            .collect::<BTreeSet<i64>>();
+
+        let mut dot = String::from("digraph intcode {\n    node [shape=box fontname=monospace];\n");
+
+        for block in self.blocks.values() {
+            let label = block
+                .instructions
+                .iter()
+                .map(|x| format!("{}\\l", x))
+                .collect::<String>();
+            let mut attributes = format!("label=\"{}\"", label);
+
+            if modified.contains(&block.start) {
+                attributes.push_str(" color=red");
+            }
+            if block.returns {
+                attributes.push_str(" peripheries=2");
+            }
+
+            writeln!(dot, "    b{} [{}];", block.start, attributes).unwrap();
+        }
+
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Next | EdgeKind::Jump => "",
+                EdgeKind::Call => " [style=dashed label=call]",
+                EdgeKind::Return => " [style=dotted label=return]",
+            };
+
+            writeln!(dot, "    b{} -> b{}{};", edge.from, edge.to, style).unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn split_blocks(instructions: Vec<Entry>) -> BTreeMap<i64, Block> {
+    // Anywhere something might jump to, or come back to, starts a block
+    let mut leaders = BTreeSet::new();
+
+    for entry in &instructions {
+        if let Entry::Instruction {
+            address,
+            opcode,
+            parameters,
+        } = entry
+        {
+            if ends_block(*opcode) {
+                leaders.extend(disasm::successors(*address, *opcode, parameters));
+                leaders.insert(address + entry.size() as i64);
+            }
+            leaders.extend(disasm::constant_pointer(*opcode, parameters));
+        }
+    }
+
+    let mut blocks = BTreeMap::<i64, Block>::new();
+    let mut current: Option<Block> = None;
+
+    for entry in instructions {
+        let address = entry.address();
+        let carry_on = current.as_ref().is_some_and(|x| {
+            x.end == address
+                && !leaders.contains(&address)
+                && !x.instructions.last().is_some_and(|x| match x {
+                    Entry::Instruction { opcode, .. } => ends_block(*opcode),
+                    Entry::Data { .. } => false,
+                })
+        });
+
+        if !carry_on {
+            if let Some(block) = current.take() {
+                blocks.insert(block.start, block);
+            }
+        }
+
+        let block = current.get_or_insert_with(|| Block {
+            start: address,
+            end: address,
+            instructions: Vec::new(),
+            reads: BTreeSet::new(),
+            writes: BTreeSet::new(),
+            indirect: false,
+            returns: false,
+        });
+
+        if let Entry::Instruction {
+            opcode, parameters, ..
+        } = &entry
+        {
+            let kinds = opcode.parameters();
+
+            for ((mode, kind), value) in opcode.modes().iter().zip(kinds).zip(parameters) {
+                match (mode, kind) {
+                    (ParameterMode::Position, Parameter::Read) => block.reads.insert(*value),
+                    (ParameterMode::Position, Parameter::Write) => block.writes.insert(*value),
+                    _ => false,
+                };
+            }
+
+            if let OpCode::JumpIfTrue(_, target) | OpCode::JumpIfFalse(_, target) = opcode {
+                let always = disasm::successors(address, *opcode, parameters)
+                    .iter()
+                    .all(|x| *x != address + 3);
+
+                block.indirect = *target != ParameterMode::Immediate;
+                block.returns = *target == ParameterMode::Relative && always;
+            }
+        }
+
+        block.end = address + entry.size() as i64;
+        block.instructions.push(entry);
+    }
+
+    if let Some(block) = current {
+        blocks.insert(block.start, block);
+    }
+
+    blocks
+}
+
+fn ends_block(opcode: OpCode) -> bool {
+    matches!(
+        opcode,
+        OpCode::JumpIfTrue(..) | OpCode::JumpIfFalse(..) | OpCode::Stop
+    )
+}
+
+fn block_edges(block: &Block, blocks: &BTreeMap<i64, Block>) -> Vec<Edge> {
+    let (address, opcode, parameters) = match block.instructions.last() {
+        Some(Entry::Instruction {
+            address,
+            opcode,
+            parameters,
+        }) => (*address, *opcode, parameters),
+        _ => return vec![],
+    };
+
+    let edge = |to, kind| Edge {
+        from: block.start,
+        to,
+        kind,
+    };
+
+    if !ends_block(opcode) {
+        return match blocks.contains_key(&block.end) {
+            true => vec![edge(block.end, EdgeKind::Next)],
+            false => vec![],
+        };
+    }
+
+    let targets = disasm::successors(address, opcode, parameters);
+
+    // An unconditional jump, after storing the address just past it
+    // through the relative base, is a call
+    if let [target] = targets[..] {
+        if target != block.end && stores_return_address(block) && blocks.contains_key(&block.end) {
+            return vec![
+                edge(target, EdgeKind::Call),
+                edge(block.end, EdgeKind::Return),
+            ];
+        }
+    }
+
+    targets
+        .into_iter()
+        .filter(|x| blocks.contains_key(x))
+        .map(|x| {
+            let kind = if x == block.end {
+                EdgeKind::Next
+            } else {
+                EdgeKind::Jump
+            };
+            edge(x, kind)
+        })
+        .collect()
+}
+
+fn stores_return_address(block: &Block) -> bool {
+    block.instructions.iter().any(|x| match x {
+        Entry::Instruction {
+            opcode: opcode @ OpCode::Add(_, _, ParameterMode::Relative),
+            parameters,
+            ..
+        }
+        | Entry::Instruction {
+            opcode: opcode @ OpCode::Multiply(_, _, ParameterMode::Relative),
+            parameters,
+            ..
+        } => disasm::constant_pointer(*opcode, parameters) == Some(block.end),
+        _ => false,
+    })
+}
+
+fn self_modifications(blocks: &BTreeMap<i64, Block>) -> Vec<SelfModification> {
+    // Which instruction each word of code belongs to
+    let mut code = BTreeMap::new();
+
+    for entry in blocks.values().flat_map(|x| &x.instructions) {
+        for word in entry.address()..entry.address() + entry.size() as i64 {
+            code.insert(word, entry.address());
+        }
+    }
+
+    let mut modifications = Vec::new();
+
+    for entry in blocks.values().flat_map(|x| &x.instructions) {
+        if let Entry::Instruction {
+            address,
+            opcode,
+            parameters,
+        } = entry
+        {
+            let writes = opcode
+                .modes()
+                .into_iter()
+                .zip(opcode.parameters())
+                .zip(parameters)
+                .filter(|x| (x.0).0 == ParameterMode::Position && (x.0).1 == Parameter::Write);
+
+            for (_, target) in writes {
+                if let Some(instruction) = code.get(target) {
+                    modifications.push(SelfModification {
+                        address: *address,
+                        target: *target,
+                        instruction: *instruction,
+                    });
+                }
+            }
+        }
+    }
+
+    modifications
+}
+
+#[test]
+fn blocks_test() {
+    // Counts down from 3, outputting each number, then overwrites its
+    // own HALT
+    let image = super::asm::assemble(
+        "
+loop:   OUT [n]
+        ADD [n], #-1, [n]
+        JNZ [n], #loop
+        ADD #0, #104, [end]
+end:    HALT
+        .data 0
+n:      .data 3",
+    )
+    .unwrap();
+
+    let analysis = Analysis::new(&image);
+
+    assert_eq!(
+        vec![0, 9],
+        analysis.blocks.keys().copied().collect::<Vec<i64>>()
+    );
+    assert_eq!(
+        vec![
+            Edge {
+                from: 0,
+                to: 0,
+                kind: EdgeKind::Jump
+            },
+            Edge {
+                from: 0,
+                to: 9,
+                kind: EdgeKind::Next
+            },
+        ],
+        analysis.edges
+    );
+    assert_eq!(
+        vec![SelfModification {
+            address: 9,
+            target: 13,
+            instruction: 13
+        }],
+        analysis.self_modifications
+    );
+    assert_eq!(
+        vec![15].into_iter().collect::<BTreeSet<i64>>(),
+        analysis.blocks[&0].reads
+    );
+    assert!(analysis.to_dot().contains("   13: HALT\\l\" color=red];"));
+}
+
+#[test]
+fn subroutine_test() {
+    // Calls `double` twice, the way compiled code does
+    let image = super::asm::assemble(
+        "
+        ARB #100
+        IN [x]
+        ADD #first, #0, rb[0]
+        JZ #0, #double
+first:  ADD #second, #0, rb[0]
+        JZ #0, #double
+second: OUT [x]
+        HALT
+double: MUL [x], #2, [x]
+        JZ #0, rb[0]
+x:      .data 0",
+    )
+    .unwrap();
+
+    let analysis = Analysis::new(&image);
+    let double = analysis.subroutines.values().next().unwrap();
+
+    assert_eq!(1, analysis.subroutines.len());
+    assert_eq!(vec![0, 11], double.callers);
+    assert_eq!(vec![double.entry], double.returns);
+    assert!(analysis.blocks[&double.entry].indirect);
+    assert!(analysis
+        .to_dot()
+        .contains("b0 -> b11 [style=dotted label=return];"));
+}
@@ -0,0 +1,53 @@
+// How ADD and MUL treat results that don't fit in an i64. Address
+// calculations (relative mode, the relative base) always fault on
+// overflow, whatever the mode.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arithmetic {
+    // Fault with `IntcodeError::Overflow`
+    #[default]
+    Checked,
+    // Two's complement wrap around, same as a release build of plain
+    // `+` and `*` would do
+    Wrapping,
+}
+
+impl Arithmetic {
+    #[inline]
+    pub fn add(self, x: i64, y: i64) -> Option<i64> {
+        match self {
+            Arithmetic::Checked => x.checked_add(y),
+            Arithmetic::Wrapping => Some(x.wrapping_add(y)),
+        }
+    }
+
+    #[inline]
+    pub fn multiply(self, x: i64, y: i64) -> Option<i64> {
+        match self {
+            Arithmetic::Checked => x.checked_mul(y),
+            Arithmetic::Wrapping => Some(x.wrapping_mul(y)),
+        }
+    }
+}
+
+#[test]
+fn overflow_test() {
+    use super::{run_program, HaltStatus, IntcodeError, Program};
+
+    // 2^62 * 4
+    let image = vec![1102, 4_611_686_018_427_387_904, 4, 7, 4, 7, 99, 0];
+
+    let mut program = Program::new(image.clone());
+    assert_eq!(
+        Err(IntcodeError::Overflow { address: 0 }),
+        run_program(&mut program).map(|_| ())
+    );
+    assert_eq!(0, program.pointer);
+
+    let mut program = Program::new(image);
+    program.arithmetic = Arithmetic::Wrapping;
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::Terminated), program.halt_status);
+    assert_eq!(Some(&0), program.output.front());
+}
@@ -0,0 +1,183 @@
+// Text I/O for programs that talk in ASCII: lines of input go in a
+// character code at a time, each followed by a newline, and output
+// comes back as a string. Anything a program outputs that isn't ASCII
+// (usually the answer, once it's done talking) is kept to one side.
+
+use super::{get_next_output, push_input, run_program, HaltStatus, Program};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Output {
+    pub text: String,
+    // Everything outside 0 to 127, in the order it came out
+    pub values: Vec<i64>,
+}
+
+// A character we can't send
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotAscii(pub char);
+
+impl fmt::Display for NotAscii {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} isn't ASCII", self.0)
+    }
+}
+
+impl Error for NotAscii {}
+
+// Queues `line` and a newline as input. Nothing is queued if any of it
+// isn't ASCII.
+pub fn send_line(program: &mut Program, line: &str) -> Result<(), NotAscii> {
+    if let Some(x) = line.chars().find(|x| !x.is_ascii()) {
+        return Err(NotAscii(x));
+    }
+
+    for x in line.bytes().chain(Some(b'\n')) {
+        push_input(program, i64::from(x));
+    }
+
+    Ok(())
+}
+
+pub fn decode(values: impl IntoIterator<Item = i64>) -> Output {
+    let mut output = Output::default();
+
+    for value in values {
+        match value {
+            0..=127 => output.text.push(value as u8 as char),
+            _ => output.values.push(value),
+        }
+    }
+
+    output
+}
+
+// Takes everything the program has output so far
+pub fn read_output(program: &mut Program) -> Output {
+    decode(std::iter::from_fn(|| get_next_output(program)))
+}
+
+// Runs the program, printing what it says to `output` and answering
+// with lines from `input`, until it stops or `input` runs out. Values
+// that aren't text get a line of their own, where they came out.
+pub fn interact(
+    program: &mut Program,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<()> {
+    let mut lines = input.lines();
+
+    loop {
+        let result = run_program(program).map(|_| ());
+
+        while let Some(value) = get_next_output(program) {
+            match value {
+                0..=127 => write!(output, "{}", value as u8 as char)?,
+                _ => writeln!(output, "\n[{}]", value)?,
+            }
+        }
+
+        if let Err(err) = result {
+            writeln!(output, "Program faulted: {}", err)?;
+            return Ok(());
+        }
+
+        match program.halt_status {
+            Some(HaltStatus::WaitingInput) => (),
+            Some(HaltStatus::Terminated) => return Ok(()),
+            status => {
+                writeln!(output, "Program stopped: {:?}", status)?;
+                return Ok(());
+            }
+        }
+
+        output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+
+        if let Err(err) = send_line(program, &line) {
+            writeln!(output, "error: {}", err)?;
+        }
+    }
+}
+
+#[test]
+fn line_test() {
+    // Echoes a line back, then outputs its length plus 1000, which
+    // isn't text
+    let mut program = Program::new(
+        super::asm::assemble(
+            "
+loop:   IN [char]
+        OUT [char]
+        EQ [char], #10, [done]
+        JNZ [done], #end
+        ADD [count], #1, [count]
+        JZ #0, #loop
+end:    ADD [count], #1000, [count]
+        OUT [count]
+        HALT
+char:   .data 0
+done:   .data 0
+count:  .data 0",
+        )
+        .unwrap(),
+    );
+
+    send_line(&mut program, "hello").unwrap();
+    run_program(&mut program).unwrap();
+
+    assert_eq!(
+        Output {
+            text: "hello\n".to_owned(),
+            values: vec![1005]
+        },
+        read_output(&mut program)
+    );
+    assert_eq!(
+        Output {
+            text: "Hi".to_owned(),
+            values: vec![128, -3]
+        },
+        decode(vec![72, 128, 105, -3])
+    );
+    assert_eq!(Err(NotAscii('é')), send_line(&mut program, "café"));
+    assert!(program.input.is_empty());
+}
+
+#[test]
+fn interact_test() {
+    // Asks for a line, says it back, and stops
+    let program = super::asm::assemble(
+        "
+        OUT #63
+        OUT #10
+loop:   IN [char]
+        OUT [char]
+        EQ [char], #10, [done]
+        JZ [done], #loop
+        OUT #-1
+        HALT
+char:   .data 0
+done:   .data 0",
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    interact(&mut Program::new(program), &b"hi there\n"[..], &mut output).unwrap();
+
+    assert_eq!("?\nhi there\n\n[-1]\n", String::from_utf8(output).unwrap());
+
+    // Values come out where they are in the text, not after it
+    let mut program = Program::new(vec![104, 72, 104, 1000, 104, 105, 99]);
+
+    let mut output = Vec::new();
+    interact(&mut program, &b""[..], &mut output).unwrap();
+
+    assert_eq!("H\n[1000]\ni", String::from_utf8(output).unwrap());
+}
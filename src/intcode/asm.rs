@@ -0,0 +1,499 @@
+// A small assembler for Intcode, using the same syntax the disassembler
+// prints:
+//
+//     loop: IN [x]              ; position mode
+//           ADD [x], #-1, rb[2] ; immediate and relative modes
+//           JNZ [rb + 2], #loop ; [rb + n] is sugar for rb[n]
+//           HALT
+//     x:    .data 0, 2 * (x - loop)
+//
+// Operands are expressions over numbers, labels, + - * / and
+// parentheses. A line may start with its address (`12:`), which is
+// checked, so disassembler listings assemble back into the same image.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    // 1-based line in the source
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(i64),
+    Label(String),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+#[derive(Debug)]
+struct Operand {
+    mode: Mode,
+    value: Expr,
+}
+
+#[derive(Debug)]
+enum Item {
+    Instruction {
+        opcode: i64,
+        // Index of the parameter that gets written to, if any
+        writes: Option<usize>,
+        operands: Vec<Operand>,
+    },
+    Data(Vec<Expr>),
+}
+
+// (mnemonic, opcode, parameter count, parameter written to)
+const INSTRUCTIONS: [(&str, i64, usize, Option<usize>); 10] = [
+    ("ADD", 1, 3, Some(2)),
+    ("MUL", 2, 3, Some(2)),
+    ("IN", 3, 1, Some(0)),
+    ("OUT", 4, 1, None),
+    ("JNZ", 5, 2, None),
+    ("JZ", 6, 2, None),
+    ("LT", 7, 3, Some(2)),
+    ("EQ", 8, 3, Some(2)),
+    ("ARB", 9, 1, None),
+    ("HALT", 99, 0, None),
+];
+
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut items = Vec::new();
+    let mut address = 0;
+
+    // First pass: parse everything and work out where the labels are
+    for (i, raw_line) in source.lines().enumerate() {
+        let line = i + 1;
+        let error = |message: String| AsmError { line, message };
+
+        let mut text = raw_line.split(';').next().unwrap().trim();
+
+        while let Some((name, rest)) = split_label(text) {
+            if let Ok(expected) = name.parse::<i64>() {
+                if expected != address {
+                    return Err(error(format!(
+                        "listed address {} doesn't match actual address {}",
+                        expected, address
+                    )));
+                }
+            } else if labels.insert(name.to_owned(), address).is_some() {
+                return Err(error(format!("duplicate label: {}", name)));
+            }
+            text = rest;
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let item = parse_item(text).map_err(error)?;
+
+        address += match &item {
+            Item::Instruction { operands, .. } => operands.len() as i64 + 1,
+            Item::Data(values) => values.len() as i64,
+        };
+
+        items.push((line, item));
+    }
+
+    // Second pass: now every label has an address, emit the words
+    let mut image = Vec::new();
+
+    for (line, item) in items {
+        let error = |message: String| AsmError { line, message };
+
+        match item {
+            Item::Instruction {
+                opcode,
+                writes,
+                operands,
+            } => {
+                let mut word = opcode;
+                let mut parameters = Vec::new();
+
+                for (i, operand) in operands.iter().enumerate() {
+                    if writes == Some(i) && operand.mode == Mode::Immediate {
+                        return Err(error(format!(
+                            "parameter {} is written to and can't be immediate",
+                            i + 1
+                        )));
+                    }
+
+                    let mode = match operand.mode {
+                        Mode::Position => 0,
+                        Mode::Immediate => 1,
+                        Mode::Relative => 2,
+                    };
+
+                    word += mode * 10_i64.pow(i as u32 + 2);
+                    parameters.push(evaluate(&operand.value, &labels).map_err(error)?);
+                }
+
+                image.push(word);
+                image.extend(parameters);
+            }
+            Item::Data(values) => {
+                for value in values {
+                    image.push(evaluate(&value, &labels).map_err(error)?);
+                }
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+// Splits off a leading `name:`, if there is one
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let colon = text.find(':')?;
+    let name = text[..colon].trim();
+
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        Some((name, text[colon + 1..].trim()))
+    } else {
+        None
+    }
+}
+
+fn parse_item(text: &str) -> Result<Item, String> {
+    let (name, rest) = match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, ""),
+    };
+
+    let arguments = split_arguments(rest)?;
+
+    if name.eq_ignore_ascii_case(".data") {
+        if arguments.is_empty() {
+            return Err(".data needs at least one value".to_owned());
+        }
+
+        return Ok(Item::Data(
+            arguments
+                .iter()
+                .map(|x| parse_expression(x))
+                .collect::<Result<Vec<Expr>, String>>()?,
+        ));
+    }
+
+    let (_, opcode, arity, writes) = INSTRUCTIONS
+        .iter()
+        .find(|x| x.0.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown instruction: {}", name))?;
+
+    if arguments.len() != *arity {
+        return Err(format!(
+            "{} takes {} operands, got {}",
+            name.to_uppercase(),
+            arity,
+            arguments.len()
+        ));
+    }
+
+    Ok(Item::Instruction {
+        opcode: *opcode,
+        writes: *writes,
+        operands: arguments
+            .iter()
+            .map(|x| parse_operand(x))
+            .collect::<Result<Vec<Operand>, String>>()?,
+    })
+}
+
+// Splits on commas that aren't nested in brackets or parentheses
+fn split_arguments(text: &str) -> Result<Vec<&str>, String> {
+    if text.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+
+        if depth < 0 {
+            return Err(format!("unbalanced brackets in: {}", text));
+        }
+    }
+
+    if depth != 0 {
+        return Err(format!("unbalanced brackets in: {}", text));
+    }
+
+    arguments.push(text[start..].trim());
+
+    match arguments.iter().find(|x| x.is_empty()) {
+        Some(_) => Err(format!("empty operand in: {}", text)),
+        None => Ok(arguments),
+    }
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand {
+            mode: Mode::Immediate,
+            value: parse_expression(value)?,
+        });
+    }
+
+    if let Some(inner) = text.strip_prefix("rb").and_then(|x| bracketed(x.trim())) {
+        return Ok(Operand {
+            mode: Mode::Relative,
+            value: parse_expression(inner)?,
+        });
+    }
+
+    let inner = bracketed(text).ok_or_else(|| {
+        format!(
+            "operand must look like [addr], #value or rb[offset]: {}",
+            text
+        )
+    })?;
+
+    // [rb], [rb + n] and [rb - n] are relative too
+    if let Some(offset) = inner.strip_prefix("rb") {
+        let offset = offset.trim();
+
+        if offset.is_empty() {
+            return Ok(Operand {
+                mode: Mode::Relative,
+                value: Expr::Number(0),
+            });
+        }
+        if offset.starts_with('+') || offset.starts_with('-') {
+            return Ok(Operand {
+                mode: Mode::Relative,
+                value: parse_expression(&format!("0 {}", offset))?,
+            });
+        }
+    }
+
+    Ok(Operand {
+        mode: Mode::Position,
+        value: parse_expression(inner)?,
+    })
+}
+
+fn bracketed(text: &str) -> Option<&str> {
+    if text.starts_with('[') && text.ends_with(']') {
+        Some(text[1..text.len() - 1].trim())
+    } else {
+        None
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// Expressions
+
+fn parse_expression(text: &str) -> Result<Expr, String> {
+    let tokens = tokenize(text)?;
+    let mut position = 0;
+
+    let expr = parse_sum(&tokens, &mut position)?;
+
+    match tokens.get(position) {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected '{}' in: {}", token, text)),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "+-*/()".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        } else {
+            return Err(format!("unexpected '{}' in: {}", c, text));
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err("missing value".to_owned());
+    }
+
+    Ok(tokens)
+}
+
+fn parse_sum(tokens: &[String], position: &mut usize) -> Result<Expr, String> {
+    let mut expr = parse_product(tokens, position)?;
+
+    while let Some(op) = tokens.get(*position).filter(|x| *x == "+" || *x == "-") {
+        *position += 1;
+        let rhs = parse_product(tokens, position)?;
+        expr = Expr::Binary(op.chars().next().unwrap(), Box::new(expr), Box::new(rhs));
+    }
+
+    Ok(expr)
+}
+
+fn parse_product(tokens: &[String], position: &mut usize) -> Result<Expr, String> {
+    let mut expr = parse_unary(tokens, position)?;
+
+    while let Some(op) = tokens.get(*position).filter(|x| *x == "*" || *x == "/") {
+        *position += 1;
+        let rhs = parse_unary(tokens, position)?;
+        expr = Expr::Binary(op.chars().next().unwrap(), Box::new(expr), Box::new(rhs));
+    }
+
+    Ok(expr)
+}
+
+fn parse_unary(tokens: &[String], position: &mut usize) -> Result<Expr, String> {
+    let token = tokens
+        .get(*position)
+        .ok_or_else(|| "expression ends early".to_owned())?;
+    *position += 1;
+
+    match token.as_ref() {
+        "-" => Ok(Expr::Negate(Box::new(parse_unary(tokens, position)?))),
+        "+" => parse_unary(tokens, position),
+        "(" => {
+            let expr = parse_sum(tokens, position)?;
+            match tokens.get(*position) {
+                Some(x) if x == ")" => {
+                    *position += 1;
+                    Ok(expr)
+                }
+                _ => Err("missing ')'".to_owned()),
+            }
+        }
+        x if x.chars().next().unwrap().is_ascii_digit() => x
+            .parse::<i64>()
+            .map(Expr::Number)
+            .map_err(|_| format!("bad number: {}", x)),
+        x if x.chars().all(|c| c.is_alphanumeric() || c == '_') => Ok(Expr::Label(x.to_owned())),
+        x => Err(format!("unexpected '{}'", x)),
+    }
+}
+
+fn evaluate(expr: &Expr, labels: &HashMap<String, i64>) -> Result<i64, String> {
+    match expr {
+        Expr::Number(x) => Ok(*x),
+        Expr::Label(name) => labels
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("undefined label: {}", name)),
+        Expr::Negate(x) => evaluate(x, labels)?
+            .checked_neg()
+            .ok_or_else(|| "arithmetic overflow".to_owned()),
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = evaluate(lhs, labels)?;
+            let rhs = evaluate(rhs, labels)?;
+
+            let result = match op {
+                '+' => lhs.checked_add(rhs),
+                '-' => lhs.checked_sub(rhs),
+                '*' => lhs.checked_mul(rhs),
+                '/' if rhs == 0 => return Err("division by zero".to_owned()),
+                _ => lhs.checked_div(rhs),
+            };
+
+            result.ok_or_else(|| "arithmetic overflow".to_owned())
+        }
+    }
+}
+
+#[test]
+fn assemble_test() {
+    // Same program as day7's first phase sequence example
+    assert_eq!(
+        Ok(vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ]),
+        assemble(
+            "
+            in [phase]
+            in [signal]       ; the input signal
+            mul [signal], #10, [signal]
+            add [signal], [phase], [phase]
+            out [phase]
+            halt
+phase:      .data 0
+signal:     .data 0"
+        )
+    );
+
+    assert_eq!(
+        Ok(vec![
+            109, 1, 204, -1, 21101, 3, -4, -1, 1105, 1, 10, 5, 11, 6, 99
+        ]),
+        assemble(
+            "
+            ARB #1
+            OUT [rb - 1]
+            ADD #end - 8, #-4, rb[end / 5 - 3]
+            JNZ #1, #(end - 4) * 2 - 3 * end + 29
+end:        .data 5, end, 2 * 3
+            HALT"
+        )
+    );
+}
+
+#[test]
+fn round_trip_test() {
+    use super::disasm;
+    use crate::util;
+
+    let image = util::comma_separated_to_vec("data/d9.txt");
+
+    assert_eq!(Ok(image.clone()), assemble(&disasm::listing(&image)));
+}
+
+#[test]
+fn error_test() {
+    let line_of = |source: &str| assemble(source).unwrap_err().line;
+
+    assert_eq!(2, line_of("HALT\nADD #1, #2, #3"));
+    assert_eq!(3, line_of("HALT\n\nJMP #0"));
+    assert_eq!(1, line_of("OUT [nowhere]"));
+    assert_eq!(2, line_of("a: HALT\na: HALT"));
+    assert_eq!(2, line_of("HALT\n5: HALT"));
+    assert_eq!(1, line_of("OUT #1, #2"));
+    assert_eq!(1, line_of("OUT (1"));
+    assert_eq!(1, line_of("OUT 1"));
+}
@@ -0,0 +1,71 @@
+use super::memory::FLAT_LIMIT;
+use super::OpCode;
+
+// Decoded opcodes by address, so each instruction word only gets parsed
+// the first time it runs. Only the opcode word itself is cached (the
+// parameters are always read fresh), so a write only has to drop the
+// entry for the address it lands on.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeCache {
+    opcodes: Vec<Option<OpCode>>,
+}
+
+impl DecodeCache {
+    #[inline]
+    pub fn get(&self, address: i64) -> Option<OpCode> {
+        if address < 0 {
+            return None;
+        }
+
+        self.opcodes.get(address as usize).copied().flatten()
+    }
+
+    pub fn insert(&mut self, address: i64, opcode: OpCode) {
+        // Code way out in sparse memory just doesn't get cached
+        if !(0..FLAT_LIMIT).contains(&address) {
+            return;
+        }
+
+        let index = address as usize;
+
+        if index >= self.opcodes.len() {
+            self.opcodes.resize(index + 1, None);
+        }
+
+        self.opcodes[index] = Some(opcode);
+    }
+
+    #[inline]
+    pub fn invalidate(&mut self, address: i64) {
+        if address >= 0 {
+            if let Some(entry) = self.opcodes.get_mut(address as usize) {
+                *entry = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.opcodes.clear();
+    }
+}
+
+#[test]
+fn cache_test() {
+    use super::ParameterMode;
+
+    let mut cache = DecodeCache::default();
+    let opcode = OpCode::Output(ParameterMode::Immediate);
+
+    assert_eq!(None, cache.get(3));
+
+    cache.insert(3, opcode);
+    assert_eq!(Some(opcode), cache.get(3));
+    assert_eq!(None, cache.get(2));
+    assert_eq!(None, cache.get(-1));
+
+    cache.invalidate(3);
+    assert_eq!(None, cache.get(3));
+
+    cache.insert(FLAT_LIMIT, opcode);
+    assert_eq!(None, cache.get(FLAT_LIMIT));
+}
@@ -0,0 +1,436 @@
+// Compiles a program's code into a tree of closures, one per
+// instruction, with the parameter words and modes baked in and grouped
+// into straight line blocks that end at a jump or HALT. Code reachable
+// from the pointer is compiled up front, anything else the first time
+// it's jumped to.
+//
+// A write that lands on compiled code throws away every block using
+// that word, and the word is left to the interpreter from then on, so
+// self-modifying code still behaves exactly as it would under
+// `run_program`. That only covers writes made by the program itself:
+// compile after patching memory from the outside, not before.
+//
+//     let mut compiled = Compiled::new(&program);
+//     compiled.run(&mut program)?;
+//
+// Programs with limits or a dialect just get interpreted, as do
+// observers, which never see compiled code run.
+
+use super::disasm;
+use super::memory::FLAT_LIMIT;
+use super::{
+    get_state, has_input, parse_opcode, relative_address, run_program, set_state, step, HaltStatus,
+    IntcodeError, OpCode, ParameterMode, Program,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+// What a compiled instruction did, besides moving the pointer
+enum Effect {
+    Continue,
+    Wrote(i64),
+    Halt(HaltStatus),
+}
+
+// A parameter, as a value or a destination address
+type Operand = Box<dyn Fn(&Program) -> Result<i64, IntcodeError>>;
+type Op = Box<dyn Fn(&mut Program) -> Result<Effect, IntcodeError>>;
+
+struct Block {
+    ops: Vec<Op>,
+    // Where it could go next, as far as we can tell without running it
+    successors: Vec<i64>,
+}
+
+#[derive(Default)]
+pub struct Compiled {
+    // None for an address the interpreter has to handle
+    blocks: HashMap<i64, Option<Rc<Block>>>,
+    // The blocks using each word
+    owners: HashMap<i64, Vec<i64>>,
+    // Words the program has written over, never compiled again
+    modified: HashSet<i64>,
+}
+
+impl Compiled {
+    pub fn new(program: &Program) -> Compiled {
+        let mut compiled = Compiled::default();
+        let mut to_visit = VecDeque::new();
+
+        to_visit.push_back(program.pointer);
+
+        while let Some(address) = to_visit.pop_front() {
+            if compiled.blocks.contains_key(&address) {
+                continue;
+            }
+
+            if let Some(block) = compiled.compile(program, address) {
+                to_visit.extend(&block.successors);
+            }
+        }
+
+        compiled
+    }
+
+    // How many blocks are compiled and still good
+    pub fn blocks(&self) -> usize {
+        self.blocks.values().filter(|x| x.is_some()).count()
+    }
+
+    // Same as `run_program`, as far as the program can tell
+    pub fn run<'a>(&mut self, program: &'a mut Program) -> Result<&'a mut Program, IntcodeError> {
+        if program.dialect().is_some() || !program.limits.is_unlimited() {
+            return run_program(program);
+        }
+
+        if program.state.is_empty() {
+            return Ok(program);
+        }
+
+        loop {
+            let block = match self.blocks.get(&program.pointer) {
+                Some(block) => block.clone(),
+                None => self.compile(program, program.pointer),
+            };
+
+            let block = match block {
+                Some(block) => block,
+                None => {
+                    let step = step(program)?;
+
+                    if let Some(write) = step.write {
+                        self.written(write.address);
+                    }
+                    if step.halt.is_some() {
+                        return Ok(program);
+                    }
+                    continue;
+                }
+            };
+
+            for op in &block.ops {
+                let effect = op(program).inspect_err(|err| {
+                    program.halt_status = Some(HaltStatus::Faulted(*err));
+                })?;
+
+                if let Effect::Halt(status) = effect {
+                    program.halt_status = Some(status);
+                    return Ok(program);
+                }
+
+                program.executed += 1;
+                program.halt_status = None;
+
+                // This block might be one of the ones that just went away
+                if let Effect::Wrote(address) = effect {
+                    if self.written(address) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Notes a write, giving whether it hit compiled code
+    fn written(&mut self, address: i64) -> bool {
+        match self.owners.remove(&address) {
+            Some(starts) => {
+                for start in starts {
+                    self.blocks.remove(&start);
+                }
+                self.modified.insert(address);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Compiles a block starting at `start`, and remembers it
+    fn compile(&mut self, program: &Program, start: i64) -> Option<Rc<Block>> {
+        let mut ops = Vec::new();
+        let mut address = start;
+        let mut successors = Vec::new();
+
+        while let Some(opcode) = self.decode(program, address) {
+            // Way out in sparse memory, or partly rewritten
+            let words = match address.checked_add(opcode.arity() as i64) {
+                Some(end) if end < FLAT_LIMIT => address..=end,
+                _ => break,
+            };
+
+            if words.clone().any(|x| self.modified.contains(&x)) {
+                break;
+            }
+
+            let parameters = words
+                .clone()
+                .skip(1)
+                .map(|x| program.state.get(x))
+                .collect::<Vec<i64>>();
+
+            ops.push(compile_op(address, opcode, &parameters));
+            successors = disasm::successors(address, opcode, &parameters);
+            address = *words.end() + 1;
+
+            // Even a jump we can't see the target of ends the block
+            if matches!(
+                opcode,
+                OpCode::JumpIfTrue(..) | OpCode::JumpIfFalse(..) | OpCode::Stop
+            ) {
+                break;
+            }
+        }
+
+        let block = match ops.len() {
+            0 => None,
+            _ => {
+                for word in start..address {
+                    self.owners.entry(word).or_default().push(start);
+                }
+
+                Some(Rc::new(Block { ops, successors }))
+            }
+        };
+
+        self.blocks.insert(start, block.clone());
+        block
+    }
+
+    fn decode(&self, program: &Program, address: i64) -> Option<OpCode> {
+        if !(0..FLAT_LIMIT).contains(&address) || self.modified.contains(&address) {
+            return None;
+        }
+
+        parse_opcode(address, program.state.get(address)).ok()
+    }
+}
+
+fn compile_op(address: i64, opcode: OpCode, parameters: &[i64]) -> Op {
+    // Blocks end before FLAT_LIMIT, so this can't overflow
+    let next = address + opcode.arity() as i64 + 1;
+    let overflow = IntcodeError::Overflow { address };
+
+    match opcode {
+        OpCode::Add(x, y, z) => binary(
+            read(x, parameters[0]),
+            read(y, parameters[1]),
+            destination(address, z, parameters[2]),
+            next,
+            move |program, x, y| program.arithmetic.add(x, y).ok_or(overflow),
+        ),
+        OpCode::Multiply(x, y, z) => binary(
+            read(x, parameters[0]),
+            read(y, parameters[1]),
+            destination(address, z, parameters[2]),
+            next,
+            move |program, x, y| program.arithmetic.multiply(x, y).ok_or(overflow),
+        ),
+        OpCode::LessThan(x, y, z) => binary(
+            read(x, parameters[0]),
+            read(y, parameters[1]),
+            destination(address, z, parameters[2]),
+            next,
+            |_, x, y| Ok((x < y) as i64),
+        ),
+        OpCode::Equals(x, y, z) => binary(
+            read(x, parameters[0]),
+            read(y, parameters[1]),
+            destination(address, z, parameters[2]),
+            next,
+            |_, x, y| Ok((x == y) as i64),
+        ),
+        OpCode::Input(x) => {
+            let destination = destination(address, x, parameters[0]);
+
+            Box::new(move |program| {
+                if !has_input(program) {
+                    return Ok(Effect::Halt(HaltStatus::WaitingInput));
+                }
+
+                let destination = destination(program)?;
+                let input = program.input.pop_front().unwrap();
+
+                set_state(program, destination, input);
+                program.pointer = next;
+                Ok(Effect::Wrote(destination))
+            })
+        }
+        OpCode::Output(x) => {
+            let value = read(x, parameters[0]);
+
+            Box::new(move |program| {
+                let value = value(program)?;
+
+                program.output.push_back(value);
+                program.pointer = next;
+                Ok(Effect::Continue)
+            })
+        }
+        OpCode::JumpIfTrue(x, y) | OpCode::JumpIfFalse(x, y) => {
+            let jump_if = matches!(opcode, OpCode::JumpIfTrue(..));
+            let (condition, target) = (read(x, parameters[0]), read(y, parameters[1]));
+
+            Box::new(move |program| {
+                let condition = condition(program)?;
+                let target = target(program)?;
+
+                program.pointer = if (condition != 0) == jump_if {
+                    target
+                } else {
+                    next
+                };
+                Ok(Effect::Continue)
+            })
+        }
+        OpCode::AdjustRelBase(x) => {
+            let offset = read(x, parameters[0]);
+
+            Box::new(move |program| {
+                let offset = offset(program)?;
+
+                program.relative_base = relative_address(program, offset)?;
+                program.pointer = next;
+                Ok(Effect::Continue)
+            })
+        }
+        OpCode::Stop => Box::new(|_| Ok(Effect::Halt(HaltStatus::Terminated))),
+        OpCode::Custom(_) | OpCode::Skip => unreachable!("only the standard set is compiled"),
+    }
+}
+
+fn binary(
+    x: Operand,
+    y: Operand,
+    destination: Operand,
+    next: i64,
+    f: impl Fn(&Program, i64, i64) -> Result<i64, IntcodeError> + 'static,
+) -> Op {
+    Box::new(move |program| {
+        let x = x(program)?;
+        let y = y(program)?;
+        let destination = destination(program)?;
+        let value = f(program, x, y)?;
+
+        set_state(program, destination, value);
+        program.pointer = next;
+        Ok(Effect::Wrote(destination))
+    })
+}
+
+fn read(mode: ParameterMode, word: i64) -> Operand {
+    match mode {
+        ParameterMode::Immediate => Box::new(move |_| Ok(word)),
+        ParameterMode::Position if word < 0 => {
+            Box::new(move |_| Err(IntcodeError::NegativeAddress { address: word }))
+        }
+        ParameterMode::Position => Box::new(move |program| Ok(program.state.get(word))),
+        ParameterMode::Relative => Box::new(move |program| {
+            get_state(
+                program,
+                relative_address(program, word)?,
+                ParameterMode::Immediate,
+            )
+        }),
+    }
+}
+
+// Same checks as `get_destination`, in the same order
+fn destination(address: i64, mode: ParameterMode, word: i64) -> Operand {
+    let check = |destination: i64| match destination {
+        x if x < 0 => Err(IntcodeError::NegativeAddress { address: x }),
+        x => Ok(x),
+    };
+
+    match mode {
+        ParameterMode::Immediate => {
+            Box::new(move |_| Err(IntcodeError::ImmediateWrite { address }))
+        }
+        ParameterMode::Position => Box::new(move |_| check(word)),
+        ParameterMode::Relative => Box::new(move |program| check(relative_address(program, word)?)),
+    }
+}
+
+// Runs a program through `Compiled` and through `run_program`,
+// checking they end up the same
+#[cfg(test)]
+fn run_both(image: &[i64], inputs: &[i64]) -> Program {
+    let mut interpreted = Program::new(image.to_vec());
+    let mut compiled = Program::new(image.to_vec());
+
+    for input in inputs {
+        super::push_input(&mut interpreted, *input);
+        super::push_input(&mut compiled, *input);
+    }
+
+    let expected = run_program(&mut interpreted).map(|_| ());
+    let result = Compiled::new(&compiled).run(&mut compiled).map(|_| ());
+
+    assert_eq!(expected, result);
+    assert_eq!(interpreted.output, compiled.output);
+    assert_eq!(interpreted.state.cells(), compiled.state.cells());
+    assert_eq!(interpreted.pointer, compiled.pointer);
+    assert_eq!(interpreted.relative_base, compiled.relative_base);
+    assert_eq!(interpreted.halt_status, compiled.halt_status);
+    assert_eq!(interpreted.executed, compiled.executed);
+
+    compiled
+}
+
+#[test]
+fn days_test() {
+    use crate::util;
+
+    // Day 2, with the 1202 alarm fixed
+    let mut image = util::comma_separated_to_vec("data/d2.txt");
+    image[1] = 12;
+    image[2] = 2;
+    run_both(&image, &[]);
+
+    let image = util::comma_separated_to_vec("data/d5.txt");
+    assert_eq!(Some(&9_938_601), run_both(&image, &[1]).output.back());
+    run_both(&image, &[5]);
+
+    // Day 7, one amplifier at a time
+    let image = util::comma_separated_to_vec("data/d7.txt");
+    let mut signal = 0;
+    for phase in &[2, 0, 1, 3, 4] {
+        signal = *run_both(&image, &[*phase, signal]).output.back().unwrap();
+    }
+
+    let image = util::comma_separated_to_vec("data/d9.txt");
+    run_both(&image, &[1]);
+    run_both(&image, &[2]);
+}
+
+#[test]
+fn self_modifying_test() {
+    use super::asm;
+
+    // Rewrites its own OUT, then a jump target in the same block it's
+    // running
+    let image = asm::assemble(
+        "
+top:    OUT #100
+        JNZ [count], #end
+        ADD #1, #0, [count]
+        ADD #4, #0, [top]
+        ADD #end, #0, [target + 2]
+target: JZ #0, #top
+        HALT
+end:    OUT #7
+        HALT
+count:  .data 0",
+    )
+    .unwrap();
+
+    let program = run_both(&image, &[]);
+    assert_eq!(
+        vec![100, 7],
+        program.output.into_iter().collect::<Vec<i64>>()
+    );
+
+    // Faults and waiting for input stop in the same place too
+    run_both(&[3, 0, 1, 0, 0, -1, 99], &[]);
+    run_both(&[3, 0, 1, 0, 0, -1, 99], &[5]);
+    run_both(&[1101, 1, 1, 7, 109, -5, 204, -1, 99], &[]);
+}
@@ -0,0 +1,455 @@
+// An interactive debugger. Reads one command per line and writes
+// everything to the given output, so it can be driven by a script on
+// stdin as easily as by hand:
+//
+//     break 12          stop before running the instruction at 12
+//     break op OUT      stop before any instruction with that mnemonic
+//     break write 100   stop after anything writes to 100
+//     watch 100         report every change to 100, without stopping
+//     delete 2          remove breakpoint 2
+//     step [n]          run n instructions (default 1), showing each
+//     back [n]          undo n instructions (default 1)
+//     continue          run until a breakpoint, halt or fault
+//     mem 100 [n]       show n cells (default 1) starting at 100
+//     set 100 42        patch a cell
+//     input [v...]      queue inputs, or show the queue if none given
+//     input clear       empty the input queue
+//     disasm [a] [n]    disassemble n instructions from a (default: here)
+//     info              pointer, relative base, status and breakpoints
+//     quit
+
+use super::disasm::{self, Entry};
+use super::journal::Journal;
+use super::{
+    decode, get_next_output, get_state, push_input, set_state, step_with, HaltStatus,
+    ParameterMode, Program,
+};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+// How many instructions `back` can undo. Older ones are forgotten, so a
+// long `continue` doesn't use up all the memory.
+const HISTORY: usize = 100_000;
+
+// The most cells `mem`, or instructions `disasm`, will show at once
+const MAX_SHOWN: i64 = 1_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    Address(i64),
+    // Matched against the disassembler's mnemonics
+    Opcode(String),
+    Write(i64),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Address(address) => write!(f, "at {}", address),
+            Breakpoint::Opcode(mnemonic) => write!(f, "on {}", mnemonic),
+            Breakpoint::Write(address) => write!(f, "on write to {}", address),
+        }
+    }
+}
+
+pub struct Debugger {
+    pub program: Program,
+    pub breakpoints: Vec<Breakpoint>,
+    pub watchpoints: Vec<i64>,
+    journal: Journal,
+    // Whether we're sitting on an address or opcode breakpoint, which
+    // shouldn't stop us again straight away
+    on_breakpoint: bool,
+}
+
+impl Debugger {
+    pub fn new(program: Program) -> Debugger {
+        Debugger {
+            program,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            journal: Journal::with_limit(HISTORY),
+            on_breakpoint: false,
+        }
+    }
+
+    // Runs commands until `quit` or the input runs out
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+
+            if let ["quit"] | ["q"] = words.as_slice() {
+                break;
+            }
+
+            if let Err(message) = self.command(&words, &mut output)? {
+                writeln!(output, "error: {}", message)?;
+            }
+
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+
+        writeln!(output)
+    }
+
+    // The outer Result is for I/O problems, the inner one for commands
+    // that didn't make sense
+    fn command(&mut self, words: &[&str], out: &mut impl Write) -> io::Result<Result<(), String>> {
+        let numbers: Vec<i64> = words
+            .iter()
+            .skip(1)
+            .map(|x| parse_number(x))
+            .collect::<Result<_, _>>()
+            .unwrap_or_default();
+
+        match (words, numbers.as_slice()) {
+            ([], _) => (),
+            (["break", "op", mnemonic], _) | (["b", "op", mnemonic], _) => {
+                self.add_breakpoint(Breakpoint::Opcode(mnemonic.to_uppercase()), out)?
+            }
+            (["break", "write", address], _) | (["b", "write", address], _) => {
+                match parse_number(address) {
+                    Ok(address) => self.add_breakpoint(Breakpoint::Write(address), out)?,
+                    Err(err) => return Ok(Err(err)),
+                }
+            }
+            (["break", _], [address]) | (["b", _], [address]) => {
+                self.add_breakpoint(Breakpoint::Address(*address), out)?
+            }
+            (["watch", _], [address]) => {
+                self.watchpoints.push(*address);
+                writeln!(out, "Watching {}", address)?;
+            }
+            (["delete", _], [index]) => {
+                if *index < 1 || *index as usize > self.breakpoints.len() {
+                    return Ok(Err(format!("no breakpoint {}", index)));
+                }
+                let removed = self.breakpoints.remove(*index as usize - 1);
+                writeln!(out, "Deleted breakpoint {} {}", index, removed)?;
+            }
+            (["step"], _) | (["s"], _) => self.resume(Some(1), out)?,
+            (["step", _], [count]) | (["s", _], [count]) => {
+                self.resume(Some((*count).max(0) as usize), out)?
+            }
+            (["back"], _) => self.back(1, out)?,
+            (["back", _], [count]) => self.back((*count).max(0) as usize, out)?,
+            (["continue"], _) | (["c"], _) => self.resume(None, out)?,
+            (["mem", _], [address]) | (["x", _], [address]) => {
+                return self.show_memory(*address, 1, out)
+            }
+            (["mem", _, _], [address, count]) | (["x", _, _], [address, count]) => {
+                return self.show_memory(*address, *count, out)
+            }
+            (["set", _, _], [address, value]) => {
+                if *address < 0 {
+                    return Ok(Err(format!("can't write to address {}", address)));
+                }
+                set_state(&mut self.program, *address, *value);
+                writeln!(out, "{:>5}: {}", address, value)?;
+            }
+            (["input", "clear"], _) => self.program.input.clear(),
+            (["input", ..], values) if values.len() == words.len() - 1 => {
+                for value in values {
+                    push_input(&mut self.program, *value);
+                }
+                writeln!(out, "Input queue: {:?}", self.program.input)?;
+            }
+            (["disasm"], _) => self.show_listing(self.program.pointer, 5, out)?,
+            (["disasm", _], [address]) => self.show_listing(*address, 5, out)?,
+            (["disasm", _, _], [address, count]) => {
+                if *count > MAX_SHOWN {
+                    return Ok(Err(format!("can't show more than {} at once", MAX_SHOWN)));
+                }
+                self.show_listing(*address, (*count).max(0) as usize, out)?
+            }
+            (["info"], _) => self.show_info(out)?,
+            _ => return Ok(Err(format!("unknown command: {}", words.join(" ")))),
+        }
+
+        Ok(Ok(()))
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "Breakpoint {} {}",
+            self.breakpoints.len() + 1,
+            breakpoint
+        )?;
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    // Runs `steps` instructions (printing each), or as many as it takes
+    // to hit something interesting if `steps` is None
+    fn resume(&mut self, steps: Option<usize>, out: &mut impl Write) -> io::Result<()> {
+        let mut count = 0;
+        let skip_breakpoint = std::mem::replace(&mut self.on_breakpoint, false);
+        let limit = steps.unwrap_or(usize::MAX);
+
+        while count < limit {
+            if steps.is_none() && (count > 0 || !skip_breakpoint) {
+                if let Some(index) = self.breakpoint_before() {
+                    self.on_breakpoint = true;
+                    writeln!(
+                        out,
+                        "Breakpoint {} {} hit",
+                        index + 1,
+                        self.breakpoints[index]
+                    )?;
+                    return self.show_listing(self.program.pointer, 1, out);
+                }
+            }
+
+            if steps.is_some() {
+                self.show_listing(self.program.pointer, 1, out)?;
+            }
+
+            let step = match step_with(&mut self.program, &mut self.journal) {
+                Ok(step) => step,
+                Err(err) => return writeln!(out, "Program faulted: {}", err),
+            };
+
+            while let Some(output) = get_next_output(&mut self.program) {
+                writeln!(out, "Output: {}", output)?;
+            }
+
+            match step.halt {
+                Some(HaltStatus::WaitingInput) => return writeln!(out, "Waiting for input"),
+                Some(HaltStatus::Terminated) => return writeln!(out, "Program terminated"),
+                Some(status) => return writeln!(out, "Program stopped: {:?}", status),
+                None => (),
+            }
+
+            if let Some(write) = step.write {
+                if self.watchpoints.contains(&write.address) && write.old != write.new {
+                    writeln!(
+                        out,
+                        "Watch {}: {} -> {}",
+                        write.address, write.old, write.new
+                    )?;
+                }
+
+                let hit = self
+                    .breakpoints
+                    .iter()
+                    .position(|x| *x == Breakpoint::Write(write.address));
+
+                if let Some(index) = hit {
+                    writeln!(
+                        out,
+                        "Breakpoint {} {} hit at {}",
+                        index + 1,
+                        self.breakpoints[index],
+                        step.address
+                    )?;
+                    return Ok(());
+                }
+            }
+
+            count += 1;
+        }
+
+        Ok(())
+    }
+
+    // Any address or opcode breakpoint on the instruction about to run
+    fn breakpoint_before(&self) -> Option<usize> {
+        let pointer = self.program.pointer;
+        let mnemonic = get_state(&self.program, pointer, ParameterMode::Immediate)
+            .and_then(|x| decode(&self.program, pointer, x))
+            .map(disasm::mnemonic)
+            .ok();
+
+        self.breakpoints.iter().position(|x| match x {
+            Breakpoint::Address(address) => *address == pointer,
+            Breakpoint::Opcode(name) => Some(name.as_ref()) == mnemonic,
+            Breakpoint::Write(_) => false,
+        })
+    }
+
+    fn back(&mut self, count: usize, out: &mut impl Write) -> io::Result<()> {
+        let undone = self.journal.rewind(&mut self.program, count);
+
+        writeln!(out, "Went back {} instructions", undone)?;
+        if undone < count {
+            writeln!(out, "No more history to go back through")?;
+        }
+        self.show_listing(self.program.pointer, 1, out)
+    }
+
+    fn show_memory(
+        &self,
+        start: i64,
+        count: i64,
+        out: &mut impl Write,
+    ) -> io::Result<Result<(), String>> {
+        if count > MAX_SHOWN {
+            return Ok(Err(format!("can't show more than {} at once", MAX_SHOWN)));
+        }
+        let end = match start.checked_add(count) {
+            Some(end) => end,
+            None => {
+                return Ok(Err(format!(
+                    "{} cells from {} is past the end",
+                    count, start
+                )))
+            }
+        };
+
+        for address in start..end {
+            match get_state(&self.program, address, ParameterMode::Immediate) {
+                Ok(value) => writeln!(out, "{:>5}: {}", address, value)?,
+                Err(err) => writeln!(out, "error: {}", err)?,
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    fn show_listing(&self, start: i64, count: usize, out: &mut impl Write) -> io::Result<()> {
+        for entry in disasm::program_listing(&self.program, start, count) {
+            let marker = match &entry {
+                Entry::Instruction { address, .. } if *address == self.program.pointer => "=>",
+                _ => "  ",
+            };
+            writeln!(out, "{}{}", marker, entry)?;
+        }
+
+        Ok(())
+    }
+
+    fn show_info(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "Pointer: {}", self.program.pointer)?;
+        writeln!(out, "Relative base: {}", self.program.relative_base)?;
+        writeln!(out, "Halt status: {:?}", self.program.halt_status)?;
+        writeln!(out, "Input queue: {:?}", self.program.input)?;
+
+        for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+            writeln!(out, "Breakpoint {} {}", i + 1, breakpoint)?;
+        }
+        for address in &self.watchpoints {
+            writeln!(out, "Watching {}", address)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    text.parse::<i64>()
+        .map_err(|_| format!("not a number: {}", text))
+}
+
+#[cfg(test)]
+fn run_script(program: Program, script: &str) -> (Debugger, String) {
+    let mut debugger = Debugger::new(program);
+    let mut output = Vec::new();
+
+    debugger.run(script.as_bytes(), &mut output).unwrap();
+
+    (debugger, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn breakpoint_test() {
+    // Counts down from its input, outputting as it goes
+    let program = Program::new(vec![
+        109, 20, 203, 0, 4, 20, 1001, 20, -1, 20, 1005, 20, 4, 99,
+    ]);
+
+    let (debugger, output) = run_script(
+        program,
+        "
+        break 6
+        continue
+        input 2
+        continue
+        mem 20
+        continue
+        delete 1
+        continue
+        quit
+        step",
+    );
+
+    assert!(output.contains("Breakpoint 1 at 6"));
+    assert!(output.contains("Waiting for input"));
+    assert!(output.contains("Output: 2\n"));
+    assert!(output.contains("Breakpoint 1 at 6 hit\n=>    6: ADD [20], #-1, [20]"));
+    assert!(output.contains("   20: 2\n"));
+    assert!(output.contains("Output: 1\n"));
+    assert!(output.contains("Deleted breakpoint 1 at 6"));
+    assert!(output.contains("Program terminated"));
+    assert_eq!(Some(HaltStatus::Terminated), debugger.program.halt_status);
+}
+
+#[test]
+fn watch_and_patch_test() {
+    let program = Program::new(vec![
+        109, 20, 203, 0, 4, 20, 1001, 20, -1, 20, 1005, 20, 4, 99,
+    ]);
+
+    let (debugger, output) = run_script(
+        program,
+        "
+        input 3
+        watch 20
+        break write 20
+        break op OUT
+        continue
+        continue
+        info
+        step 2
+        back
+        set 20 10
+        delete 2
+        c
+        c
+        disasm 0 2
+        bogus",
+    );
+
+    assert!(output.contains("Watch 20: 0 -> 3\nBreakpoint 1 on write to 20 hit at 2"));
+    assert!(output.contains("Breakpoint 2 on OUT hit"));
+    assert!(output.contains("Pointer: 4\nRelative base: 20"));
+    assert!(output.contains("Output: 3\n"));
+    assert!(output.contains("Went back 1 instructions"));
+    assert!(output.contains("   20: 10\n"));
+    assert!(output.contains("Watch 20: 10 -> 9"));
+    assert!(output.contains("=>    4: OUT [20]\n"));
+    assert!(output.contains("      0: ARB #20\n      2: IN rb[0]\n"));
+    assert!(output.contains("error: unknown command: bogus"));
+    assert_eq!(10, debugger.program.pointer);
+    assert_eq!(
+        8,
+        get_state(&debugger.program, 20, ParameterMode::Immediate).unwrap()
+    );
+}
+
+#[test]
+fn bad_range_test() {
+    let program = Program::new(vec![104, 1, 99]);
+
+    let (debugger, output) = run_script(
+        program,
+        "
+        mem 9223372036854775807 2
+        mem 0 1000000000000
+        disasm 0 1000000000000
+        step
+        back 5
+        mem 0 2",
+    );
+
+    assert!(output.contains("error: 2 cells from 9223372036854775807 is past the end"));
+    assert!(output.contains("error: can't show more than 1000 at once"));
+    assert!(output.contains("Went back 1 instructions\nNo more history"));
+    assert!(output.contains("    1: 1\n"));
+    assert_eq!(0, debugger.program.pointer);
+}
@@ -0,0 +1,460 @@
+// Instruction sets as data. A dialect maps opcode numbers to what the
+// instruction looks like (a mnemonic and its parameters) and a function
+// that runs it. The 2019 set is built in: `Dialect::standard()` maps
+// its opcodes to the interpreter's own instructions, so a dialect
+// built on it only has to say what's different:
+//
+//     let mut dialect = Dialect::standard();
+//     dialect.register(10, Instruction::new("DIV", &[Read, Read, Write], div));
+//     let program = Program::with_dialect(image, dialect);
+//
+// Instructions have at most three parameters, same as the built in
+// ones.
+
+use super::{
+    disasm, get_destination, get_state, parameter_address, parse_opcode, write_state, HaltStatus,
+    IntcodeError, OpCode, ParameterMode, Program, StepResult,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parameter {
+    // Passed to the instruction as a value, modes applied
+    Read,
+    // Passed as the address to write to
+    Write,
+}
+
+// What the pointer does once an instruction has run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    Next,
+    Jump(i64),
+    // Stop without running, leaving the pointer where it is
+    Halt(HaltStatus),
+}
+
+// Gets the parameters in order, as given by `Instruction::parameters`.
+// Anything the journal should be able to undo has to go through
+// `write`, and into `StepResult::input`/`output`/`relative_base`.
+pub type Execute = fn(&mut Program, &mut StepResult, &[i64]) -> Result<Flow, IntcodeError>;
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub mnemonic: &'static str,
+    pub parameters: Vec<Parameter>,
+    implementation: Implementation,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Implementation {
+    // The standard instruction with this opcode, decoded to the
+    // matching `OpCode` and run by the interpreter itself
+    Builtin(i64),
+    Custom(Execute),
+}
+
+impl Instruction {
+    pub fn new(mnemonic: &'static str, parameters: &[Parameter], execute: Execute) -> Instruction {
+        assert!(parameters.len() <= 3, "too many parameters");
+
+        Instruction {
+            mnemonic,
+            parameters: parameters.to_vec(),
+            implementation: Implementation::Custom(execute),
+        }
+    }
+
+    // One of the standard instructions, by its usual opcode. It can be
+    // registered under a different one.
+    pub fn builtin(code: i64) -> Instruction {
+        let opcode = parse_opcode(0, code).expect("not a standard opcode");
+
+        Instruction {
+            mnemonic: disasm::mnemonic(opcode),
+            parameters: opcode.parameters(),
+            implementation: Implementation::Builtin(code),
+        }
+    }
+}
+
+// What to do with an opcode that isn't in the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unknown {
+    // Fault with `UnknownOpcode`
+    Reject,
+    // Treat the word as a one word no-op
+    Skip,
+}
+
+// An instruction decoded through a dialect. Everything but `execute` is
+// copied out of the table, so it can live in the decode cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CustomOp {
+    pub code: i64,
+    pub mnemonic: &'static str,
+    pub arity: usize,
+    pub modes: [ParameterMode; 3],
+    pub parameters: [Parameter; 3],
+}
+
+#[derive(Debug, Clone)]
+pub struct Dialect {
+    instructions: BTreeMap<i64, Instruction>,
+    pub unknown: Unknown,
+}
+
+impl Dialect {
+    // No instructions at all, not even HALT
+    pub fn empty() -> Dialect {
+        Dialect {
+            instructions: BTreeMap::new(),
+            unknown: Unknown::Reject,
+        }
+    }
+
+    // The 2019 instruction set
+    pub fn standard() -> Dialect {
+        let mut dialect = Dialect::empty();
+
+        for code in &[1, 2, 3, 4, 5, 6, 7, 8, 9, 99] {
+            dialect.register(*code, Instruction::builtin(*code));
+        }
+
+        dialect
+    }
+
+    // The standard set, plus division, bitwise operations and an
+    // indirect jump:
+    //
+    //     10 DIV a, b, dest   (rounds towards zero)
+    //     11 MOD a, b, dest   (sign follows a)
+    //     12 AND a, b, dest
+    //     13 OR  a, b, dest
+    //     14 XOR a, b, dest
+    //     15 JMP target       (so JMP [x] jumps to wherever x points)
+    pub fn extended() -> Dialect {
+        use Parameter::{Read, Write};
+
+        let mut dialect = Dialect::standard();
+
+        dialect.register(10, Instruction::new("DIV", &[Read, Read, Write], divide));
+        dialect.register(11, Instruction::new("MOD", &[Read, Read, Write], remainder));
+        dialect.register(12, Instruction::new("AND", &[Read, Read, Write], and));
+        dialect.register(13, Instruction::new("OR", &[Read, Read, Write], or));
+        dialect.register(14, Instruction::new("XOR", &[Read, Read, Write], xor));
+        dialect.register(15, Instruction::new("JMP", &[Read], jump));
+
+        dialect
+    }
+
+    // Adds an instruction, returning whatever had that opcode before.
+    // Opcodes are the last two digits of a word, so 1 to 99. Mnemonics
+    // have to be one word, so listings and traces can be read back.
+    pub fn register(&mut self, code: i64, instruction: Instruction) -> Option<Instruction> {
+        assert!((1..100).contains(&code), "opcode out of range: {}", code);
+        assert!(
+            !instruction.mnemonic.is_empty() && !instruction.mnemonic.contains(char::is_whitespace),
+            "mnemonic isn't one word: {:?}",
+            instruction.mnemonic
+        );
+
+        self.instructions.insert(code, instruction)
+    }
+
+    pub fn remove(&mut self, code: i64) -> Option<Instruction> {
+        self.instructions.remove(&code)
+    }
+
+    pub fn get(&self, code: i64) -> Option<&Instruction> {
+        self.instructions.get(&code)
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = (i64, &Instruction)> {
+        self.instructions.iter().map(|(code, x)| (*code, x))
+    }
+
+    pub fn decode(&self, address: i64, word: i64) -> Result<OpCode, IntcodeError> {
+        let unknown = || match self.unknown {
+            Unknown::Reject => Err(IntcodeError::UnknownOpcode {
+                address,
+                opcode: word,
+            }),
+            Unknown::Skip => Ok(OpCode::Skip),
+        };
+
+        let instruction = match self.get(word % 100) {
+            Some(instruction) if word >= 0 => instruction,
+            _ => return unknown(),
+        };
+
+        if let Implementation::Builtin(code) = instruction.implementation {
+            // Same modes, but the opcode it has in the standard set. A
+            // word that's already near the top might not have room.
+            let standard = match word
+                .checked_sub(word % 100)
+                .and_then(|x| x.checked_add(code))
+            {
+                Some(standard) => standard,
+                None => return unknown(),
+            };
+
+            return match parse_opcode(address, standard) {
+                Err(IntcodeError::UnknownOpcode { .. }) => unknown(),
+                x => x,
+            };
+        }
+
+        let arity = instruction.parameters.len();
+        let mut op = CustomOp {
+            code: word % 100,
+            mnemonic: instruction.mnemonic,
+            arity,
+            modes: [ParameterMode::Position; 3],
+            parameters: [Parameter::Read; 3],
+        };
+        let mut modes = word / 100;
+
+        for i in 0..arity {
+            op.modes[i] = super::parse_mode(address, modes % 10)?;
+            op.parameters[i] = instruction.parameters[i];
+            modes /= 10;
+        }
+
+        // More mode digits than parameters
+        if modes != 0 {
+            return unknown();
+        }
+
+        Ok(OpCode::Custom(op))
+    }
+}
+
+// For instructions to store their results
+pub fn write(program: &mut Program, step: &mut StepResult, address: i64, value: i64) {
+    write_state(program, step, address, value);
+}
+
+// Runs a decoded instruction, once `execute` has checked the limits
+pub(super) fn run(
+    program: &mut Program,
+    step: &mut StepResult,
+    op: CustomOp,
+) -> Result<(), IntcodeError> {
+    let implementation = program
+        .dialect()
+        .and_then(|x| x.get(op.code))
+        .map(|x| x.implementation);
+
+    let execute = match implementation {
+        Some(Implementation::Custom(execute)) => execute,
+        // Only if the dialect changed under an already decoded word
+        _ => {
+            return Err(IntcodeError::UnknownOpcode {
+                address: program.pointer,
+                opcode: op.code,
+            })
+        }
+    };
+
+    for i in 0..op.arity {
+        let pointer = parameter_address(program, 1 + i as i64)?;
+
+        step.operands[i] = match op.parameters[i] {
+            Parameter::Read => get_state(program, pointer, op.modes[i])?,
+            Parameter::Write => get_destination(program, pointer, op.modes[i])?,
+        };
+    }
+
+    let operands = step.operands;
+
+    match execute(program, step, &operands[..op.arity])? {
+        Flow::Next => program.pointer = parameter_address(program, op.arity as i64 + 1)?,
+        Flow::Jump(target) => program.pointer = target,
+        Flow::Halt(status) => step.halt = Some(status),
+    }
+
+    Ok(())
+}
+
+fn overflow(program: &Program) -> IntcodeError {
+    IntcodeError::Overflow {
+        address: program.pointer,
+    }
+}
+
+fn divide(program: &mut Program, step: &mut StepResult, x: &[i64]) -> Result<Flow, IntcodeError> {
+    if x[1] == 0 {
+        return Err(IntcodeError::DivideByZero {
+            address: program.pointer,
+        });
+    }
+
+    let quotient = x[0].checked_div(x[1]).ok_or_else(|| overflow(program))?;
+    write(program, step, x[2], quotient);
+    Ok(Flow::Next)
+}
+
+fn remainder(
+    program: &mut Program,
+    step: &mut StepResult,
+    x: &[i64],
+) -> Result<Flow, IntcodeError> {
+    if x[1] == 0 {
+        return Err(IntcodeError::DivideByZero {
+            address: program.pointer,
+        });
+    }
+
+    let remainder = x[0].checked_rem(x[1]).ok_or_else(|| overflow(program))?;
+    write(program, step, x[2], remainder);
+    Ok(Flow::Next)
+}
+
+fn and(program: &mut Program, step: &mut StepResult, x: &[i64]) -> Result<Flow, IntcodeError> {
+    write(program, step, x[2], x[0] & x[1]);
+    Ok(Flow::Next)
+}
+
+fn or(program: &mut Program, step: &mut StepResult, x: &[i64]) -> Result<Flow, IntcodeError> {
+    write(program, step, x[2], x[0] | x[1]);
+    Ok(Flow::Next)
+}
+
+fn xor(program: &mut Program, step: &mut StepResult, x: &[i64]) -> Result<Flow, IntcodeError> {
+    write(program, step, x[2], x[0] ^ x[1]);
+    Ok(Flow::Next)
+}
+
+fn jump(_: &mut Program, _: &mut StepResult, x: &[i64]) -> Result<Flow, IntcodeError> {
+    Ok(Flow::Jump(x[0]))
+}
+
+#[test]
+fn standard_table_test() {
+    use super::{get_next_output, push_input, run_program};
+
+    // Day 9's quine, and its comparison example, through both paths
+    let quine = vec![
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+    let compare = vec![
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+        1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
+        1105, 1, 46, 98, 99,
+    ];
+
+    for (image, input) in [(quine, 0), (compare.clone(), 7), (compare, 9)] {
+        let mut fast = Program::new(image.clone());
+        let mut table = Program::with_dialect(image, Dialect::standard());
+
+        push_input(&mut fast, input);
+        push_input(&mut table, input);
+        run_program(&mut fast).unwrap();
+        run_program(&mut table).unwrap();
+
+        assert_eq!(fast.output, table.output);
+        assert_eq!(fast.executed, table.executed);
+        assert_eq!(Some(HaltStatus::Terminated), table.halt_status);
+    }
+
+    // Waiting for input works the same way too
+    let mut table = Program::with_dialect(vec![3, 0, 4, 0, 99], Dialect::standard());
+    run_program(&mut table).unwrap();
+    assert_eq!(Some(HaltStatus::WaitingInput), table.halt_status);
+    push_input(&mut table, 5);
+    run_program(&mut table).unwrap();
+    assert_eq!(Some(5), get_next_output(&mut table));
+}
+
+#[test]
+fn extended_test() {
+    use super::{run_program, step};
+
+    // 17 / 5, 17 % 5, 12 ^ 10, then jump to wherever 23 points
+    let image = vec![
+        1110, 17, 5, 20, 1111, 17, 5, 21, 1114, 12, 10, 22, 15, 23, 0, 0, 0, 0, 99, 0, 0, 0, 0, 18,
+    ];
+    let mut program = Program::with_dialect(image, Dialect::extended());
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::Terminated), program.halt_status);
+    assert_eq!(3, program.state.get(20));
+    assert_eq!(2, program.state.get(21));
+    assert_eq!(6, program.state.get(22));
+    assert_eq!(18, program.pointer);
+
+    // Dividing by zero faults
+    let mut program = Program::with_dialect(vec![1110, 1, 0, 0, 99], Dialect::extended());
+    assert_eq!(
+        Err(IntcodeError::DivideByZero { address: 0 }),
+        step(&mut program)
+    );
+}
+
+#[test]
+fn unknown_opcode_test() {
+    use super::run_program;
+
+    // 42 isn't anything
+    let image = vec![42, 1101, 1, 1, 0, 99];
+
+    let mut program = Program::with_dialect(image.clone(), Dialect::standard());
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode {
+            address: 0,
+            opcode: 42
+        }),
+        run_program(&mut program).map(|_| ())
+    );
+
+    let mut dialect = Dialect::standard();
+    dialect.unknown = Unknown::Skip;
+
+    let mut program = Program::with_dialect(image, dialect);
+    assert_eq!(Ok(OpCode::Skip), super::decode(&program, 0, 42));
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::Terminated), program.halt_status);
+    assert_eq!(2, program.state.get(0));
+    assert_eq!(5, program.pointer);
+}
+
+#[test]
+fn builtin_test() {
+    use super::step;
+
+    // Adds 3 and 4 into the last cell
+    let mut program = Program::new(vec![1101, 3, 4, 5, 99, 0]);
+    step(&mut program).unwrap();
+    assert_eq!(7, program.state.get(5));
+
+    // Now with 1 meaning MUL. The ADD that was already decoded has to
+    // be forgotten.
+    let mut dialect = Dialect::standard();
+    dialect.register(1, Instruction::builtin(2));
+
+    program.pointer = 0;
+    program.set_dialect(Some(dialect));
+    step(&mut program).unwrap();
+    assert_eq!(12, program.state.get(5));
+
+    // 7 as HALT, with a word too big to turn into 99
+    let mut dialect = Dialect::standard();
+    dialect.register(7, Instruction::builtin(99));
+
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode {
+            address: 0,
+            opcode: i64::MAX
+        }),
+        dialect.decode(0, i64::MAX)
+    );
+}
+
+#[test]
+#[should_panic(expected = "mnemonic isn't one word")]
+fn mnemonic_test() {
+    Dialect::standard().register(20, Instruction::new("NO OP", &[], |_, _, _| Ok(Flow::Next)));
+}
@@ -0,0 +1,371 @@
+use super::{get_state, parse_opcode, IntcodeError, OpCode, ParameterMode, Program};
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+
+// One line of a listing: either an instruction we decoded, or a word
+// we couldn't (or didn't) treat as code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry {
+    Instruction {
+        address: i64,
+        opcode: OpCode,
+        // The raw parameter words, modes not applied
+        parameters: Vec<i64>,
+    },
+    Data {
+        address: i64,
+        value: i64,
+    },
+}
+
+impl Entry {
+    pub fn address(&self) -> i64 {
+        match self {
+            Entry::Instruction { address, .. } | Entry::Data { address, .. } => *address,
+        }
+    }
+
+    // How many words of the image this entry covers
+    pub fn size(&self) -> usize {
+        match self {
+            Entry::Instruction { parameters, .. } => parameters.len() + 1,
+            Entry::Data { .. } => 1,
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Instruction {
+                address,
+                opcode,
+                parameters,
+            } => {
+                write!(f, "{:>5}: {}", address, mnemonic(*opcode))?;
+
+                for (i, (mode, value)) in opcode.modes().iter().zip(parameters).enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}", separator, operand(*mode, *value))?;
+                }
+
+                Ok(())
+            }
+            Entry::Data { address, value } => write!(f, "{:>5}: .data {}", address, value),
+        }
+    }
+}
+
+pub fn mnemonic(opcode: OpCode) -> &'static str {
+    match opcode {
+        OpCode::Add(..) => "ADD",
+        OpCode::Multiply(..) => "MUL",
+        OpCode::Input(_) => "IN",
+        OpCode::Output(_) => "OUT",
+        OpCode::JumpIfTrue(..) => "JNZ",
+        OpCode::JumpIfFalse(..) => "JZ",
+        OpCode::LessThan(..) => "LT",
+        OpCode::Equals(..) => "EQ",
+        OpCode::AdjustRelBase(_) => "ARB",
+        OpCode::Stop => "HALT",
+        OpCode::Custom(op) => op.mnemonic,
+        OpCode::Skip => "NOP",
+    }
+}
+
+// [12] for position mode, #5 for immediate, rb[3] for relative
+pub fn operand(mode: ParameterMode, value: i64) -> String {
+    match mode {
+        ParameterMode::Position => format!("[{}]", value),
+        ParameterMode::Immediate => format!("#{}", value),
+        ParameterMode::Relative => format!("rb[{}]", value),
+    }
+}
+
+// Decodes the instruction at `address`, if there is one and all of its
+// parameters fit inside the image.
+pub fn decode(image: &[i64], address: i64) -> Option<Entry> {
+    decode_with(
+        |x| image.get(usize::try_from(x).ok()?).copied(),
+        parse_opcode,
+        address,
+    )
+}
+
+// Like `decode`, but reading words through `read`, which gives None for
+// anything outside the memory being looked at, and turning them into
+// opcodes with `parse`
+pub fn decode_with(
+    read: impl Fn(i64) -> Option<i64>,
+    parse: impl Fn(i64, i64) -> Result<OpCode, IntcodeError>,
+    address: i64,
+) -> Option<Entry> {
+    let opcode = parse(address, read(address)?).ok()?;
+    let parameters = (1..=opcode.arity() as i64)
+        .map(|i| read(address.checked_add(i)?))
+        .collect::<Option<Vec<i64>>>()?;
+
+    Some(Entry::Instruction {
+        address,
+        opcode,
+        parameters,
+    })
+}
+
+// A linear sweep over a running program's memory. Stops early if it
+// reaches the end of the address space.
+pub fn program_listing(program: &Program, start: i64, count: usize) -> Vec<Entry> {
+    let read = |x| get_state(program, x, ParameterMode::Immediate).ok();
+    let mut entries = Vec::new();
+    let mut address = start.max(0);
+
+    while entries.len() < count {
+        let parse = |address, word| super::decode(program, address, word);
+        let entry = decode_with(read, parse, address).unwrap_or(Entry::Data {
+            address,
+            value: read(address).unwrap_or(0),
+        });
+        let next = address.checked_add(entry.size() as i64);
+
+        entries.push(entry);
+        address = match next {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    entries
+}
+
+// Decodes everything in order, starting from `start`, without trying
+// to tell code from data. Handy for looking at a small window of memory.
+pub fn linear_sweep(image: &[i64], start: i64, count: usize) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut address = start.max(0);
+
+    while entries.len() < count && (address as usize) < image.len() {
+        let entry = decode(image, address).unwrap_or(Entry::Data {
+            address,
+            value: image[address as usize],
+        });
+
+        address += entry.size() as i64;
+        entries.push(entry);
+    }
+
+    entries
+}
+
+// Walks the program from address 0, following every jump whose target
+// we can work out statically. Anything never reached is listed as
+// data.
+pub fn disassemble(image: &[i64]) -> Vec<Entry> {
+    let mut code = BTreeMap::new();
+    // Every word claimed by an instruction, so we don't decode into the
+    // middle of one
+    let mut covered = vec![false; image.len()];
+    let mut to_visit = VecDeque::new();
+    // Addresses that only look like code pointers. These are tried once
+    // everything definitely reachable has been claimed.
+    let mut maybe_code = VecDeque::new();
+
+    to_visit.push_back(0);
+
+    while let Some(address) = to_visit.pop_front().or_else(|| maybe_code.pop_front()) {
+        if address < 0 || address as usize >= image.len() || covered[address as usize] {
+            continue;
+        }
+
+        let entry = match decode(image, address) {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        let span = address as usize..address as usize + entry.size();
+
+        if covered[span.clone()].iter().any(|x| *x) {
+            continue;
+        }
+
+        for word in span {
+            covered[word] = true;
+        }
+
+        if let Entry::Instruction {
+            opcode, parameters, ..
+        } = &entry
+        {
+            to_visit.extend(successors(address, *opcode, parameters));
+            maybe_code.extend(constant_pointer(*opcode, parameters));
+        }
+
+        code.insert(address, entry);
+    }
+
+    let mut entries = Vec::new();
+    let mut address = 0;
+
+    while (address as usize) < image.len() {
+        let entry = code.remove(&address).unwrap_or(Entry::Data {
+            address,
+            value: image[address as usize],
+        });
+
+        address += entry.size() as i64;
+        entries.push(entry);
+    }
+
+    entries
+}
+
+pub fn listing(image: &[i64]) -> String {
+    disassemble(image)
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Where execution might go after this instruction
+pub(super) fn successors(address: i64, opcode: OpCode, parameters: &[i64]) -> Vec<i64> {
+    let next = address + opcode.arity() as i64 + 1;
+
+    match opcode {
+        OpCode::Stop => vec![],
+        OpCode::JumpIfTrue(condition, target) | OpCode::JumpIfFalse(condition, target) => {
+            let jump_if = matches!(opcode, OpCode::JumpIfTrue(..));
+            let mut targets = Vec::new();
+
+            // A jump on an immediate is either always or never taken
+            let taken = match condition {
+                ParameterMode::Immediate => Some((parameters[0] != 0) == jump_if),
+                _ => None,
+            };
+
+            if taken != Some(false) && target == ParameterMode::Immediate {
+                targets.push(parameters[1]);
+            }
+            if taken != Some(true) {
+                targets.push(next);
+            }
+
+            targets
+        }
+        _ => vec![next],
+    }
+}
+
+// Constants built from two immediates and stored through the relative
+// base are usually return addresses being pushed before a call, so
+// they're worth a look. Stored anywhere else they're just as likely to
+// be plain numbers.
+pub(super) fn constant_pointer(opcode: OpCode, parameters: &[i64]) -> Option<i64> {
+    use ParameterMode::{Immediate, Relative};
+
+    match opcode {
+        OpCode::Add(Immediate, Immediate, Relative) => parameters[0].checked_add(parameters[1]),
+        OpCode::Multiply(Immediate, Immediate, Relative) => {
+            parameters[0].checked_mul(parameters[1])
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn formatting_test() {
+    assert_eq!(
+        "    0: ADD [12], #5, rb[3]",
+        decode(&[21001, 12, 5, 3], 0).unwrap().to_string()
+    );
+    assert_eq!(
+        "    4: .data 42",
+        Entry::Data {
+            address: 4,
+            value: 42
+        }
+        .to_string()
+    );
+    assert_eq!("    0: HALT", decode(&[99], 0).unwrap().to_string());
+    assert!(decode(&[1, 0, 0], 0).is_none());
+}
+
+#[test]
+fn code_and_data_test() {
+    // The jump skips over two data words, and the final 42 is never
+    // reached
+    let image = vec![1105, 1, 5, 7, 8, 4, 3, 99, 42];
+
+    assert_eq!(
+        "    0: JNZ #1, #5
+    3: .data 7
+    4: .data 8
+    5: OUT [3]
+    7: HALT
+    8: .data 42",
+        listing(&image)
+    );
+}
+
+#[test]
+fn linear_sweep_test() {
+    let image = vec![1105, 1, 5, 7, 8, 4, 3, 99, 42];
+
+    assert_eq!(
+        vec![0, 3, 7],
+        linear_sweep(&image, 0, 3)
+            .iter()
+            .map(Entry::address)
+            .collect::<Vec<i64>>()
+    );
+}
+
+#[test]
+fn end_of_memory_test() {
+    let mut program = Program::new(vec![]);
+    super::set_state(&mut program, i64::MAX - 1, 1101);
+
+    // The ADD's parameters would be past the end, so it's data, and the
+    // listing stops at the last address
+    assert_eq!(
+        vec![
+            Entry::Data {
+                address: i64::MAX - 1,
+                value: 1101
+            },
+            Entry::Data {
+                address: i64::MAX,
+                value: 0
+            }
+        ],
+        program_listing(&program, i64::MAX - 1, 3)
+    );
+}
+
+#[test]
+fn return_address_test() {
+    // Pushes 7 as a return address, calls 10, which jumps back through
+    // the relative base
+    let image = vec![21101, 0, 7, 0, 1105, 1, 10, 104, 1, 99, 2106, 0, 0];
+
+    let addresses = disassemble(&image)
+        .iter()
+        .filter(|x| matches!(x, Entry::Instruction { .. }))
+        .map(Entry::address)
+        .collect::<Vec<i64>>();
+
+    assert_eq!(vec![0, 4, 7, 9, 10], addresses);
+
+    // The same constant stored to a plain address isn't a pointer, so
+    // the OUT it happens to point at stays data
+    let image = vec![1101, 3, 4, 9, 99, 0, 0, 104, 5, 0];
+
+    assert_eq!(
+        vec![0, 4],
+        disassemble(&image)
+            .iter()
+            .filter(|x| matches!(x, Entry::Instruction { .. }))
+            .map(Entry::address)
+            .collect::<Vec<i64>>()
+    );
+}
@@ -0,0 +1,507 @@
+// Differential fuzzing. Random (but well formed) programs are run
+// through the interpreter, the compiler and a reference interpreter
+// that's written to be obviously right rather than fast, and anything
+// they disagree on is shrunk down to a small program that shows it.
+//
+// Every case comes from a seed, so a failure can be reproduced with
+// `check(&generate(&mut StdRng::seed_from_u64(seed)))`.
+
+use super::compiler::Compiled;
+use super::{push_input, run_program, HaltStatus, IntcodeError, Program};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+// Random programs loop forever often enough that everything is run
+// with a budget
+pub const MAX_STEPS: u64 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub image: Vec<i64>,
+    pub inputs: Vec<i64>,
+}
+
+// Everything about a finished run that we compare
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub halt: HaltStatus,
+    pub pointer: i64,
+    pub relative_base: i64,
+    pub executed: u64,
+    pub output: Vec<i64>,
+    pub input: Vec<i64>,
+    // Non-zero cells only, as the interpreter's memory keeps zeros
+    // around that the reference never wrote
+    pub memory: BTreeMap<i64, i64>,
+}
+
+impl Outcome {
+    fn of(program: &Program) -> Outcome {
+        Outcome {
+            halt: program.halt_status.expect("program still running"),
+            pointer: program.pointer,
+            relative_base: program.relative_base,
+            executed: program.executed,
+            output: program.output.iter().copied().collect(),
+            input: program.input.iter().copied().collect(),
+            memory: program
+                .state
+                .cells()
+                .into_iter()
+                .filter(|x| x.1 != 0)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub seed: u64,
+    pub original: Case,
+    pub shrunk: Case,
+    // What disagreed, for the shrunk case
+    pub difference: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed {}: {}", self.seed, self.difference)?;
+        writeln!(f, "image: {:?}", self.shrunk.image)?;
+        write!(f, "inputs: {:?}", self.shrunk.inputs)
+    }
+}
+
+// Runs the cases for every seed in `seeds`, stopping at the first one
+// that fails
+pub fn fuzz(seeds: Range<u64>) -> Result<(), Box<Failure>> {
+    for seed in seeds {
+        let case = generate(&mut StdRng::seed_from_u64(seed));
+
+        if check(&case).is_err() {
+            let shrunk = shrink(&case, |x| check(x).is_err());
+
+            return Err(Box::new(Failure {
+                seed,
+                difference: check(&shrunk).unwrap_err(),
+                original: case,
+                shrunk,
+            }));
+        }
+    }
+
+    Ok(())
+}
+
+// A handful of instructions, some data after them, and some input
+pub fn generate(rng: &mut impl Rng) -> Case {
+    let count = rng.gen_range(1, 12);
+    let mut opcodes = (0..count)
+        .map(|_| [1, 2, 3, 4, 5, 6, 7, 8, 9][rng.gen_range(0, 9)])
+        .collect::<Vec<i64>>();
+    opcodes.push(99);
+
+    let starts = opcodes
+        .iter()
+        .scan(0, |address, x| {
+            let start = *address;
+            *address += arity(*x) as i64 + 1;
+            Some(start)
+        })
+        .collect::<Vec<i64>>();
+    let code_length = starts.last().unwrap() + 1;
+    let length = code_length + rng.gen_range(0, 8);
+
+    let mut image = Vec::new();
+
+    for code in opcodes {
+        let mut modes = 0;
+        let mut parameters = Vec::new();
+
+        for i in 0..arity(code) {
+            let writes = matches!((code, i), (3, 0) | (1, 2) | (2, 2) | (7, 2) | (8, 2));
+            let jump_target = matches!((code, i), (5, 1) | (6, 1));
+
+            // Written parameters are only immediate by mistake
+            let mode = match rng.gen_range(0, 100) {
+                x if writes && x < 80 => 0,
+                x if writes && x < 98 => 2,
+                _ if writes => 1,
+                x if x < 50 => 0,
+                x if x < 80 => 1,
+                _ => 2,
+            };
+
+            let parameter = match mode {
+                1 if jump_target => starts[rng.gen_range(0, starts.len())],
+                1 if code == 9 => rng.gen_range(-4, 5),
+                1 if rng.gen_bool(0.05) => [i64::MAX, i64::MIN, 1 << 62][rng.gen_range(0, 3)],
+                1 => rng.gen_range(-20, 20),
+                2 => rng.gen_range(-4, length + 4),
+                _ => rng.gen_range(0, length + 4),
+            };
+
+            modes += mode * 10_i64.pow(i as u32);
+            parameters.push(parameter);
+        }
+
+        image.push(code + modes * 100);
+        image.extend(parameters);
+    }
+
+    while (image.len() as i64) < length {
+        image.push(rng.gen_range(-20, 20));
+    }
+
+    let inputs = (0..rng.gen_range(0, 4))
+        .map(|_| rng.gen_range(-10, 10))
+        .collect();
+
+    Case { image, inputs }
+}
+
+// Checks every way of running `case` agrees with the reference
+pub fn check(case: &Case) -> Result<(), String> {
+    let expected = reference(case, MAX_STEPS);
+
+    let mut program = load(case);
+    program.limits.max_instructions = Some(MAX_STEPS);
+    let _ = run_program(&mut program);
+
+    compare("interpreter", &expected, &Outcome::of(&program))?;
+
+    // Without a budget, the compiler just hands over to the
+    // interpreter, so it only gets programs that stop on their own
+    if expected.halt != HaltStatus::InstructionLimit {
+        let mut program = load(case);
+        let _ = Compiled::new(&program).run(&mut program);
+
+        compare("compiler", &expected, &Outcome::of(&program))?;
+    }
+
+    Ok(())
+}
+
+fn load(case: &Case) -> Program {
+    let mut program = Program::new(case.image.clone());
+
+    for input in &case.inputs {
+        push_input(&mut program, *input);
+    }
+
+    program
+}
+
+fn compare(name: &str, expected: &Outcome, actual: &Outcome) -> Result<(), String> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} disagrees with the reference\nexpected: {:?}\nactual:   {:?}",
+            name, expected, actual
+        ))
+    }
+}
+
+// Makes `case` as small as it'll go while `fails` still holds: fewer
+// inputs, a shorter image, and smaller words
+pub fn shrink(case: &Case, fails: impl Fn(&Case) -> bool) -> Case {
+    let mut case = case.clone();
+
+    loop {
+        let candidates = smaller(&case);
+
+        match candidates.into_iter().find(|x| fails(x)) {
+            Some(smaller) => case = smaller,
+            None => return case,
+        }
+    }
+}
+
+// Everything one step smaller than `case`, most promising first
+fn smaller(case: &Case) -> Vec<Case> {
+    let mut candidates = Vec::new();
+
+    for i in 0..case.inputs.len() {
+        let mut inputs = case.inputs.clone();
+        inputs.remove(i);
+        candidates.push(Case {
+            image: case.image.clone(),
+            inputs,
+        });
+    }
+
+    // Whole instructions first, then single words. An empty program
+    // doesn't run at all, so keep at least one word.
+    for size in (1..=4).rev().filter(|x| *x < case.image.len()) {
+        for i in (0..=case.image.len() - size).rev() {
+            let mut image = case.image.clone();
+            image.drain(i..i + size);
+            candidates.push(Case {
+                image,
+                inputs: case.inputs.clone(),
+            });
+        }
+    }
+
+    for (i, word) in case.image.iter().enumerate() {
+        for replacement in &[0, word / 2] {
+            if replacement != word {
+                let mut image = case.image.clone();
+                image[i] = *replacement;
+                candidates.push(Case {
+                    image,
+                    inputs: case.inputs.clone(),
+                });
+            }
+        }
+    }
+
+    candidates
+}
+
+fn arity(code: i64) -> usize {
+    match code {
+        1 | 2 | 7 | 8 => 3,
+        5 | 6 => 2,
+        3 | 4 | 9 => 1,
+        _ => 0,
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// The reference interpreter. Everything is done longhand, one
+// instruction at a time, without sharing any code with the real one.
+
+struct Reference {
+    memory: HashMap<i64, i64>,
+    pointer: i64,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    output: Vec<i64>,
+    executed: u64,
+}
+
+pub fn reference(case: &Case, max_steps: u64) -> Outcome {
+    let mut machine = Reference {
+        memory: (0..).zip(case.image.iter().copied()).collect(),
+        pointer: 0,
+        relative_base: 0,
+        input: case.inputs.iter().copied().collect(),
+        output: Vec::new(),
+        executed: 0,
+    };
+
+    let halt = loop {
+        match machine.step(max_steps) {
+            Ok(None) => (),
+            Ok(Some(halt)) => break halt,
+            Err(err) => break HaltStatus::Faulted(err),
+        }
+    };
+
+    Outcome {
+        halt,
+        pointer: machine.pointer,
+        relative_base: machine.relative_base,
+        executed: machine.executed,
+        output: machine.output,
+        input: machine.input.into_iter().collect(),
+        memory: machine.memory.into_iter().filter(|x| x.1 != 0).collect(),
+    }
+}
+
+impl Reference {
+    fn step(&mut self, max_steps: u64) -> Result<Option<HaltStatus>, IntcodeError> {
+        let address = self.pointer;
+        let word = self.load(address)?;
+        let unknown = IntcodeError::UnknownOpcode {
+            address,
+            opcode: word,
+        };
+
+        if word <= 0 {
+            return Err(unknown);
+        }
+
+        let code = word % 100;
+
+        // Whatever's in front of a 99 doesn't matter
+        if code == 99 {
+            return Ok(Some(HaltStatus::Terminated));
+        }
+
+        let arity = match code {
+            1..=9 => arity(code),
+            _ => return Err(unknown),
+        };
+
+        if word / 10_i64.pow(arity as u32 + 2) != 0 {
+            return Err(unknown);
+        }
+
+        let mut modes = Vec::new();
+
+        for i in 0..arity {
+            let mode = word / 10_i64.pow(i as u32 + 2) % 10;
+
+            if mode > 2 {
+                return Err(IntcodeError::InvalidParameterMode { address, mode });
+            }
+
+            modes.push(mode);
+        }
+
+        // Waiting for input doesn't run anything, so it's never held
+        // back by the limit
+        if code == 3 && self.input.is_empty() {
+            return Ok(Some(HaltStatus::WaitingInput));
+        }
+
+        // Limits are only checked once the instruction has decoded
+        if self.executed >= max_steps {
+            return Ok(Some(HaltStatus::InstructionLimit));
+        }
+
+        let overflow = IntcodeError::Overflow { address };
+
+        match code {
+            1 | 2 | 7 | 8 => {
+                let a = self.read(1, modes[0])?;
+                let b = self.read(2, modes[1])?;
+                let destination = self.destination(3, modes[2])?;
+
+                let value = match code {
+                    1 => a.checked_add(b).ok_or(overflow)?,
+                    2 => a.checked_mul(b).ok_or(overflow)?,
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+
+                self.pointer = self.parameter(4)?;
+                self.memory.insert(destination, value);
+            }
+            3 => {
+                let destination = self.destination(1, modes[0])?;
+                self.pointer = self.parameter(2)?;
+
+                let value = self.input.pop_front().unwrap();
+                self.memory.insert(destination, value);
+            }
+            4 => {
+                let value = self.read(1, modes[0])?;
+
+                self.pointer = self.parameter(2)?;
+                self.output.push(value);
+            }
+            5 | 6 => {
+                let condition = self.read(1, modes[0])?;
+                let target = self.read(2, modes[1])?;
+
+                if (condition != 0) == (code == 5) {
+                    self.pointer = target;
+                } else {
+                    self.pointer = self.parameter(3)?;
+                }
+            }
+            _ => {
+                let offset = self.read(1, modes[0])?;
+
+                self.relative_base = self.relative_base.checked_add(offset).ok_or(overflow)?;
+                self.pointer = self.parameter(2)?;
+            }
+        }
+
+        self.executed += 1;
+
+        Ok(None)
+    }
+
+    fn load(&self, address: i64) -> Result<i64, IntcodeError> {
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress { address });
+        }
+
+        Ok(self.memory.get(&address).copied().unwrap_or(0))
+    }
+
+    // The value of parameter `n` of the current instruction
+    fn read(&self, n: i64, mode: i64) -> Result<i64, IntcodeError> {
+        let parameter = self.load(self.parameter(n)?)?;
+
+        match mode {
+            0 => self.load(parameter),
+            1 => Ok(parameter),
+            _ => self.load(self.relative(parameter)?),
+        }
+    }
+
+    // Where parameter `n` of the current instruction says to write
+    fn destination(&self, n: i64, mode: i64) -> Result<i64, IntcodeError> {
+        let parameter = self.load(self.parameter(n)?)?;
+
+        let destination = match mode {
+            0 => parameter,
+            1 => {
+                return Err(IntcodeError::ImmediateWrite {
+                    address: self.pointer,
+                })
+            }
+            _ => self.relative(parameter)?,
+        };
+
+        if destination < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                address: destination,
+            });
+        }
+
+        Ok(destination)
+    }
+
+    fn relative(&self, offset: i64) -> Result<i64, IntcodeError> {
+        self.relative_base
+            .checked_add(offset)
+            .ok_or(IntcodeError::Overflow {
+                address: self.pointer,
+            })
+    }
+
+    // Where word `n` of the current instruction is
+    fn parameter(&self, n: i64) -> Result<i64, IntcodeError> {
+        self.pointer.checked_add(n).ok_or(IntcodeError::Overflow {
+            address: self.pointer,
+        })
+    }
+}
+
+#[test]
+fn fuzz_test() {
+    if let Err(failure) = fuzz(0..1000) {
+        panic!("{}", failure);
+    }
+}
+
+#[test]
+fn shrink_test() {
+    // Anything that outputs a 7 shrinks down to just doing that
+    let case = Case {
+        image: vec![1101, 3, 4, 20, 104, 7, 3, 21, 99, 5, 5],
+        inputs: vec![1, 2],
+    };
+    let outputs_seven = |x: &Case| reference(x, MAX_STEPS).output.contains(&7);
+
+    assert_eq!(
+        Case {
+            image: vec![104, 7],
+            inputs: vec![]
+        },
+        shrink(&case, outputs_seven)
+    );
+
+    // Same seed, same case
+    let mut rng = StdRng::seed_from_u64(7);
+    assert_eq!(generate(&mut rng), generate(&mut StdRng::seed_from_u64(7)));
+}
@@ -0,0 +1,230 @@
+// Where a program's inputs come from and its outputs go. A program
+// keeps its own input and output queues; `run_program_io` feeds the
+// input queue from an `IntcodeIo` whenever the program blocks, and
+// hands everything it outputs straight over. That way the plain queues
+// still work as before (and the journal and snapshots still see them),
+// but a program can just as well be wired to a channel, a closure or
+// the terminal.
+
+use super::{get_next_output, push_input, run_program, HaltStatus, IntcodeError, Program};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+pub trait IntcodeIo {
+    // The next input, or None if there isn't one yet. The program will
+    // be left waiting for input.
+    fn read(&mut self) -> Option<i64>;
+
+    fn write(&mut self, value: i64);
+}
+
+// The in-memory queue. Reads come off the front, writes go on the back.
+impl IntcodeIo for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+
+    fn write(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+// Inputs from one queue, outputs to another, for when the two ends
+// shouldn't see each other's values
+#[derive(Debug, Clone, Default)]
+pub struct Queues {
+    pub input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+}
+
+impl Queues {
+    pub fn new(input: impl IntoIterator<Item = i64>) -> Queues {
+        Queues {
+            input: input.into_iter().collect(),
+            output: VecDeque::new(),
+        }
+    }
+}
+
+impl IntcodeIo for Queues {
+    fn read(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    fn write(&mut self, value: i64) {
+        self.output.push_back(value);
+    }
+}
+
+// Reading never blocks: an empty channel leaves the program waiting
+// for input, so it can be run again once something's been sent.
+// Outputs sent after the other end has gone away are dropped.
+#[derive(Debug)]
+pub struct ChannelIo {
+    pub input: Receiver<i64>,
+    pub output: Sender<i64>,
+}
+
+impl ChannelIo {
+    pub fn new(input: Receiver<i64>, output: Sender<i64>) -> ChannelIo {
+        ChannelIo { input, output }
+    }
+}
+
+impl IntcodeIo for ChannelIo {
+    fn read(&mut self) -> Option<i64> {
+        self.input.try_recv().ok()
+    }
+
+    fn write(&mut self, value: i64) {
+        let _ = self.output.send(value);
+    }
+}
+
+// A pair of closures, for when setting up a type would be overkill
+pub struct FnIo<R, W> {
+    read: R,
+    write: W,
+}
+
+impl<R, W> FnIo<R, W>
+where
+    R: FnMut() -> Option<i64>,
+    W: FnMut(i64),
+{
+    pub fn new(read: R, write: W) -> FnIo<R, W> {
+        FnIo { read, write }
+    }
+}
+
+impl<R, W> IntcodeIo for FnIo<R, W>
+where
+    R: FnMut() -> Option<i64>,
+    W: FnMut(i64),
+{
+    fn read(&mut self) -> Option<i64> {
+        (self.read)()
+    }
+
+    fn write(&mut self, value: i64) {
+        (self.write)(value)
+    }
+}
+
+// One number per line on stdin, one number per line on stdout. Blank
+// lines are skipped, and the end of stdin (or something that isn't a
+// number) leaves the program waiting for input.
+#[derive(Debug, Default)]
+pub struct StdIo;
+
+impl IntcodeIo for StdIo {
+    fn read(&mut self) -> Option<i64> {
+        let stdin = io::stdin();
+
+        for line in stdin.lock().lines() {
+            match line.ok()?.trim() {
+                "" => continue,
+                x => return x.parse().ok(),
+            }
+        }
+
+        None
+    }
+
+    fn write(&mut self, value: i64) {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+
+        let _ = writeln!(stdout, "{}", value).and_then(|_| stdout.flush());
+    }
+}
+
+// Runs the program, writing its outputs to `io` and reading from `io`
+// whenever it runs out of input. Stops when the program terminates or
+// faults, or when `io` has nothing more to give.
+pub fn run_program_io<'a>(
+    program: &'a mut Program,
+    io: &mut dyn IntcodeIo,
+) -> Result<&'a mut Program, IntcodeError> {
+    loop {
+        let result = run_program(program).map(|_| ());
+
+        while let Some(value) = get_next_output(program) {
+            io.write(value);
+        }
+
+        result?;
+
+        if program.halt_status != Some(HaltStatus::WaitingInput) {
+            return Ok(program);
+        }
+
+        match io.read() {
+            Some(value) => push_input(program, value),
+            None => return Ok(program),
+        }
+    }
+}
+
+#[test]
+fn queues_test() {
+    // Adds pairs of inputs together, forever
+    let mut program = Program::new(vec![3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 1105, 1, 0]);
+    let mut queues = Queues::new(vec![1, 2, 30, 40, 5]);
+
+    run_program_io(&mut program, &mut queues).unwrap();
+
+    assert_eq!(vec![3, 70], queues.output.into_iter().collect::<Vec<i64>>());
+    assert!(queues.input.is_empty());
+    // Still holding onto the 5 while it waits for the other half
+    assert_eq!(Some(HaltStatus::WaitingInput), program.halt_status);
+    assert_eq!(
+        5,
+        super::get_state(&program, 13, super::ParameterMode::Immediate).unwrap()
+    );
+}
+
+#[test]
+fn channel_test() {
+    use std::sync::mpsc::channel;
+
+    // Doubles every input it gets
+    let image = vec![3, 9, 1002, 9, 2, 9, 4, 9, 1105, 1, 0];
+
+    // Two doublers in a row, so everything comes out four times bigger
+    let (to_first, first_input) = channel();
+    let (to_second, second_input) = channel();
+    let (to_main, results) = channel();
+
+    let mut first = Program::new(image.clone());
+    let mut second = Program::new(image);
+    let mut first_io = ChannelIo::new(first_input, to_second);
+    let mut second_io = ChannelIo::new(second_input, to_main);
+
+    for x in 1..=3 {
+        to_first.send(x).unwrap();
+    }
+
+    run_program_io(&mut first, &mut first_io).unwrap();
+    run_program_io(&mut second, &mut second_io).unwrap();
+
+    assert_eq!(vec![4, 8, 12], results.try_iter().collect::<Vec<i64>>());
+}
+
+#[test]
+fn closure_test() {
+    // Outputs its input, then stops
+    let mut program = Program::new(vec![3, 5, 4, 5, 99, 0]);
+    let mut inputs = vec![7];
+    let mut outputs = vec![];
+
+    run_program_io(
+        &mut program,
+        &mut FnIo::new(|| inputs.pop(), |x| outputs.push(x)),
+    )
+    .unwrap();
+
+    assert_eq!(vec![7], outputs);
+    assert_eq!(Some(HaltStatus::Terminated), program.halt_status);
+}
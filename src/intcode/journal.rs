@@ -0,0 +1,200 @@
+// An undo log for running programs backwards. Attach it with
+// `run_program_with` or `step_with`; plain `run_program` never pays for
+// it.
+//
+// Undoing a step restores the cell it wrote, the pointer, the relative
+// base, puts any input it consumed back at the front of the queue, and
+// takes back its output if the caller hasn't already read it.
+
+use super::{set_state, Observer, Program, StepResult};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    steps: VecDeque<StepResult>,
+    // Only keep this many of the most recent steps, if set
+    limit: Option<usize>,
+}
+
+impl Journal {
+    pub fn new() -> Journal {
+        Journal::default()
+    }
+
+    pub fn with_limit(limit: usize) -> Journal {
+        Journal {
+            steps: VecDeque::new(),
+            limit: Some(limit),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    // Oldest first. Indexes here are what `rewind_to` takes.
+    pub fn steps(&self) -> impl Iterator<Item = &StepResult> {
+        self.steps.iter()
+    }
+
+    // Undoes the most recent step, returning it
+    pub fn step_back(&mut self, program: &mut Program) -> Option<StepResult> {
+        let step = self.steps.pop_back()?;
+
+        if let Some(write) = step.write {
+            set_state(program, write.address, write.old);
+        }
+        if let Some((before, _)) = step.relative_base {
+            program.relative_base = before;
+        }
+        if let Some(input) = step.input {
+            program.input.push_front(input);
+        }
+        // Outputs come off the front, so if there's anything left at all
+        // the newest one is ours
+        if step.output.is_some() {
+            program.output.pop_back();
+        }
+
+        program.pointer = step.address;
+        program.halt_status = None;
+        program.executed = program.executed.saturating_sub(1);
+
+        Some(step)
+    }
+
+    // Undoes up to `count` steps, returning how many actually were
+    pub fn rewind(&mut self, program: &mut Program, count: usize) -> usize {
+        (0..count)
+            .take_while(|_| self.step_back(program).is_some())
+            .count()
+    }
+
+    // Puts the program back to just before step `index` ran
+    pub fn rewind_to(&mut self, program: &mut Program, index: usize) {
+        let count = self.len().saturating_sub(index);
+        self.rewind(program, count);
+    }
+
+    pub fn last_write_to(&self, address: i64) -> Option<usize> {
+        self.steps
+            .iter()
+            .rposition(|x| x.write.map(|w| w.address) == Some(address))
+    }
+
+    pub fn last_output_of(&self, value: i64) -> Option<usize> {
+        self.steps.iter().rposition(|x| x.output == Some(value))
+    }
+}
+
+impl Observer for Journal {
+    fn after_step(&mut self, _program: &Program, step: &StepResult) {
+        // Stopping or waiting on input doesn't change anything
+        if step.halt.is_some() {
+            return;
+        }
+
+        if let Some(limit) = self.limit {
+            // A limit of zero keeps nothing at all
+            if limit == 0 {
+                return;
+            }
+            if self.steps.len() >= limit {
+                self.steps.pop_front();
+            }
+        }
+
+        self.steps.push_back(step.clone());
+    }
+}
+
+#[test]
+fn rewind_test() {
+    use super::{get_state, push_input, run_program_with, HaltStatus, ParameterMode};
+
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Takes a number and counts down from it, outputting as it goes
+    let image = vec![109, 20, 203, 0, 4, 20, 1001, 20, -1, 20, 1005, 20, 4, 99];
+    let original = super::Program::new(image);
+    let mut program = original.clone();
+    let mut journal = Journal::new();
+
+    push_input(&mut program, 3);
+    run_program_with(&mut program, &mut journal).unwrap();
+
+    assert_eq!(
+        vec![3, 2, 1],
+        program.output.iter().copied().collect::<Vec<i64>>()
+    );
+    assert_eq!(Some(HaltStatus::Terminated), program.halt_status);
+
+    // Back to the step that printed the 2
+    let index = journal.last_output_of(2).unwrap();
+    journal.rewind_to(&mut program, index);
+
+    assert_eq!(
+        vec![3],
+        program.output.iter().copied().collect::<Vec<i64>>()
+    );
+    assert_eq!(
+        2,
+        get_state(&program, 20, ParameterMode::Immediate).unwrap()
+    );
+    assert_eq!(4, program.pointer);
+
+    // The last write to the counter is the decrement that made it 2
+    let write = journal.last_write_to(20).unwrap();
+    assert_eq!(6, journal.steps().nth(write).unwrap().address);
+
+    // All the way back is where we started, input included
+    journal.rewind(&mut program, usize::MAX);
+
+    assert!(journal.is_empty());
+    // Memory grew to fit the counter, but it's all zeros again
+    let nonzero = |program: &Program| {
+        program
+            .state
+            .cells()
+            .into_iter()
+            .filter(|x| x.1 != 0)
+            .collect::<Vec<(i64, i64)>>()
+    };
+    assert_eq!(nonzero(&original), nonzero(&program));
+    assert_eq!((0, 0), (program.pointer, program.relative_base));
+    assert_eq!(Some(&3), program.input.front());
+    assert!(program.output.is_empty());
+
+    // And running forwards again gets the same answer
+    run_program_with(&mut program, &mut journal).unwrap();
+    assert_eq!(
+        vec![3, 2, 1],
+        program.output.iter().copied().collect::<Vec<i64>>()
+    );
+}
+
+#[test]
+fn limit_test() {
+    let mut program = super::Program::new(vec![1101, 1, 1, 7, 1105, 1, 0, 0]);
+    let mut journal = Journal::with_limit(3);
+
+    for _ in 0..10 {
+        super::step_with(&mut program, &mut journal).unwrap();
+    }
+
+    assert_eq!(3, journal.len());
+    assert_eq!(3, journal.rewind(&mut program, 5));
+
+    let mut journal = Journal::with_limit(0);
+
+    for _ in 0..10 {
+        super::step_with(&mut program, &mut journal).unwrap();
+    }
+
+    assert!(journal.is_empty());
+    assert_eq!(0, journal.rewind(&mut program, 5));
+}
@@ -0,0 +1,184 @@
+// Budgets for running programs we don't trust. Each one is checked
+// before an instruction runs; running out stops the program with its
+// own `HaltStatus`, leaving the pointer on the instruction that would
+// have gone over. Raise the limit (or drain the output) and run it
+// again to carry on.
+
+use super::dialect::Parameter;
+use super::{get_destination, has_input, parameter_address, HaltStatus, OpCode, Program};
+use std::time::Instant;
+
+// The deadline is only looked at this often, as reading the clock
+// costs more than most instructions. It has to be a power of two, as
+// the check masks off the low bits
+const DEADLINE_INTERVAL: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    // Counted against `Program::executed`, over the program's whole
+    // life rather than per run
+    pub max_instructions: Option<u64>,
+    // As counted by `Memory::len`
+    pub max_memory_cells: Option<usize>,
+    // Outputs waiting in the queue, so reading them makes room
+    pub max_output: Option<usize>,
+    pub deadline: Option<Instant>,
+}
+
+impl Limits {
+    #[inline]
+    pub fn is_unlimited(&self) -> bool {
+        self.max_instructions.is_none()
+            && self.max_memory_cells.is_none()
+            && self.max_output.is_none()
+            && self.deadline.is_none()
+    }
+}
+
+// Whether running `opcode` next would go over any of the program's
+// limits
+#[inline]
+pub fn exceeded(program: &Program, opcode: OpCode) -> Option<HaltStatus> {
+    let limits = &program.limits;
+
+    if limits.is_unlimited() {
+        return None;
+    }
+
+    // Halting, or waiting for input, doesn't run anything, so it can't
+    // go over a budget
+    match opcode {
+        OpCode::Stop => return None,
+        OpCode::Input(_) if !has_input(program) => return None,
+        _ => (),
+    }
+
+    if limits
+        .max_instructions
+        .is_some_and(|x| program.executed >= x)
+    {
+        return Some(HaltStatus::InstructionLimit);
+    }
+
+    if let (Some(max), OpCode::Output(_)) = (limits.max_output, opcode) {
+        if program.output.len() >= max {
+            return Some(HaltStatus::OutputLimit);
+        }
+    }
+
+    if let Some(max) = limits.max_memory_cells {
+        // A bad destination is left for the instruction itself to
+        // fault on
+        let parameters = opcode.parameters().into_iter().zip(opcode.modes());
+
+        for (i, (parameter, mode)) in parameters.enumerate() {
+            if parameter != Parameter::Write {
+                continue;
+            }
+
+            let destination = parameter_address(program, 1 + i as i64)
+                .and_then(|x| get_destination(program, x, mode))
+                .ok();
+
+            if destination.is_some_and(|x| program.state.len_after_set(x) > max) {
+                return Some(HaltStatus::MemoryLimit);
+            }
+        }
+    }
+
+    if let Some(deadline) = limits.deadline {
+        if program.executed & (DEADLINE_INTERVAL - 1) == 0 && Instant::now() >= deadline {
+            return Some(HaltStatus::DeadlineExceeded);
+        }
+    }
+
+    None
+}
+
+#[test]
+fn instruction_limit_test() {
+    use super::run_program;
+
+    // Counts up forever
+    let mut program = Program::new(vec![1001, 7, 1, 7, 1105, 1, 0, 0]);
+    program.limits.max_instructions = Some(100);
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::InstructionLimit), program.halt_status);
+    assert_eq!(100, program.executed);
+    assert_eq!(0, program.pointer);
+    assert_eq!(50, program.state.get(7));
+
+    // Giving it more lets it carry on from where it was
+    program.limits.max_instructions = Some(200);
+    run_program(&mut program).unwrap();
+
+    assert_eq!(100, program.state.get(7));
+    assert_eq!(200, program.executed);
+
+    // Stopping right on the limit, or waiting for input there, isn't
+    // going over it
+    let mut program = Program::new(vec![1101, 1, 1, 9, 3, 9, 1101, 1, 1, 9]);
+    program.limits.max_instructions = Some(1);
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::WaitingInput), program.halt_status);
+    assert_eq!(4, program.pointer);
+
+    super::push_input(&mut program, 5);
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::InstructionLimit), program.halt_status);
+    assert_eq!(4, program.pointer);
+
+    let mut program = Program::new(vec![1101, 1, 1, 5, 99, 0]);
+    program.limits.max_instructions = Some(1);
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::Terminated), program.halt_status);
+    assert_eq!(1, program.executed);
+}
+
+#[test]
+fn memory_and_output_limit_test() {
+    use super::{get_next_output, run_program};
+
+    // Writes to an address way out past the end
+    let mut program = Program::new(vec![1101, 1, 1, 100_000, 99]);
+    program.limits.max_memory_cells = Some(1000);
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::MemoryLimit), program.halt_status);
+    assert_eq!(5, program.state.len());
+
+    // Outputs zeros forever
+    let mut program = Program::new(vec![104, 0, 1105, 1, 0]);
+    program.limits.max_output = Some(3);
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::OutputLimit), program.halt_status);
+    assert_eq!(3, program.output.len());
+
+    get_next_output(&mut program);
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::OutputLimit), program.halt_status);
+    assert_eq!(3, program.output.len());
+}
+
+#[test]
+fn deadline_test() {
+    use super::run_program;
+
+    let mut program = Program::new(vec![1105, 1, 0]);
+    program.limits.deadline = Some(Instant::now());
+
+    run_program(&mut program).unwrap();
+
+    assert_eq!(Some(HaltStatus::DeadlineExceeded), program.halt_status);
+}
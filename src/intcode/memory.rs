@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt;
+
+// Where a program keeps its cells. Anything that was never written
+// reads as zero.
+pub trait Memory: fmt::Debug + Send {
+    fn get(&self, address: i64) -> i64;
+
+    // Returns whatever was there before
+    fn set(&mut self, address: i64, value: i64) -> i64;
+
+    // Number of cells that are actually being stored
+    fn len(&self) -> usize;
+
+    // What `len` would be after writing to `address`
+    fn len_after_set(&self, address: i64) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Every stored cell, in address order
+    fn cells(&self) -> Vec<(i64, i64)>;
+
+    fn boxed_clone(&self) -> Box<dyn Memory>;
+
+    fn to_map(&self) -> HashMap<i64, i64> {
+        self.cells().into_iter().collect()
+    }
+}
+
+impl Clone for Box<dyn Memory> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+// Anything at or above this (or below zero) goes into the sparse
+// overflow rather than growing the vector. 1M cells is 8MB, far more
+// than any real program uses for contiguous memory.
+pub const FLAT_LIMIT: i64 = 1 << 20;
+
+// A vector for the low addresses where programs actually live, with a
+// hash map for the odd write far off in the distance
+#[derive(Debug, Clone, Default)]
+pub struct FlatMemory {
+    cells: Vec<i64>,
+    sparse: HashMap<i64, i64>,
+}
+
+impl From<Vec<i64>> for FlatMemory {
+    fn from(cells: Vec<i64>) -> FlatMemory {
+        FlatMemory {
+            cells,
+            sparse: HashMap::new(),
+        }
+    }
+}
+
+impl Memory for FlatMemory {
+    #[inline]
+    fn get(&self, address: i64) -> i64 {
+        match self.cells.get(address as usize) {
+            Some(x) => *x,
+            None if is_sparse(address) => *self.sparse.get(&address).unwrap_or(&0),
+            None => 0,
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, address: i64, value: i64) -> i64 {
+        if is_sparse(address) {
+            return self.sparse.insert(address, value).unwrap_or(0);
+        }
+
+        let index = address as usize;
+
+        if index >= self.cells.len() {
+            self.cells.resize(index + 1, 0);
+        }
+
+        std::mem::replace(&mut self.cells[index], value)
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len() + self.sparse.len()
+    }
+
+    fn len_after_set(&self, address: i64) -> usize {
+        if is_sparse(address) {
+            self.len() + !self.sparse.contains_key(&address) as usize
+        } else {
+            self.cells.len().max(address as usize + 1) + self.sparse.len()
+        }
+    }
+
+    fn cells(&self) -> Vec<(i64, i64)> {
+        let mut sparse = self
+            .sparse
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect::<Vec<_>>();
+        sparse.sort();
+
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (i as i64, *x))
+            .chain(sparse)
+            .collect()
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Memory> {
+        Box::new(self.clone())
+    }
+}
+
+#[inline]
+fn is_sparse(address: i64) -> bool {
+    !(0..FLAT_LIMIT).contains(&address)
+}
+
+// Every cell in a hash map. Slower, but doesn't care how spread out the
+// addresses are.
+#[derive(Debug, Clone, Default)]
+pub struct SparseMemory {
+    cells: HashMap<i64, i64>,
+}
+
+impl From<Vec<i64>> for SparseMemory {
+    fn from(cells: Vec<i64>) -> SparseMemory {
+        SparseMemory {
+            cells: cells
+                .into_iter()
+                .enumerate()
+                .map(|(i, x)| (i as i64, x))
+                .collect(),
+        }
+    }
+}
+
+impl Memory for SparseMemory {
+    fn get(&self, address: i64) -> i64 {
+        *self.cells.get(&address).unwrap_or(&0)
+    }
+
+    fn set(&mut self, address: i64, value: i64) -> i64 {
+        self.cells.insert(address, value).unwrap_or(0)
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn len_after_set(&self, address: i64) -> usize {
+        self.len() + !self.cells.contains_key(&address) as usize
+    }
+
+    fn cells(&self) -> Vec<(i64, i64)> {
+        let mut cells = self.cells.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        cells.sort();
+        cells
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Memory> {
+        Box::new(self.clone())
+    }
+}
+
+#[test]
+fn flat_memory_test() {
+    let mut memory = FlatMemory::from(vec![1, 2, 3]);
+
+    assert_eq!(3, memory.get(2));
+    assert_eq!(0, memory.get(10));
+    assert_eq!(3, memory.len());
+
+    assert_eq!(0, memory.set(5, 42));
+    assert_eq!(42, memory.get(5));
+    assert_eq!(6, memory.len());
+
+    // Way out past the end shouldn't allocate all the cells in between
+    assert_eq!(0, memory.set(FLAT_LIMIT * 1000, 7));
+    assert_eq!(7, memory.set(FLAT_LIMIT * 1000, 8));
+    assert_eq!(8, memory.get(FLAT_LIMIT * 1000));
+    assert_eq!(7, memory.len());
+
+    assert_eq!(
+        vec![(0, 1), (1, 2), (2, 3), (5, 42), (FLAT_LIMIT * 1000, 8)],
+        memory
+            .cells()
+            .into_iter()
+            .filter(|x| x.1 != 0)
+            .collect::<Vec<(i64, i64)>>()
+    );
+}
+
+#[test]
+fn backends_agree_test() {
+    let mut flat: Box<dyn Memory> = Box::new(FlatMemory::from(vec![5, 6]));
+    let mut sparse: Box<dyn Memory> = Box::new(SparseMemory::from(vec![5, 6]));
+
+    for (address, value) in [(1, 9), (100, 3), (FLAT_LIMIT + 1, -4), (-3, 2), (0, 0)] {
+        assert_eq!(flat.set(address, value), sparse.set(address, value));
+    }
+
+    for address in [-3, 0, 1, 2, 100, FLAT_LIMIT, FLAT_LIMIT + 1] {
+        assert_eq!(flat.get(address), sparse.get(address));
+    }
+
+    assert_eq!(flat.len() + 1, flat.len_after_set(-4));
+    assert_eq!(flat.len(), flat.len_after_set(FLAT_LIMIT + 1));
+    assert_eq!(sparse.len() + 1, sparse.len_after_set(7));
+
+    let copy = flat.clone();
+    flat.set(1, 0);
+    assert_eq!(9, copy.get(1));
+}
@@ -0,0 +1,746 @@
+// Runs a bunch of programs wired together. Each node is run until it
+// blocks on input, its outputs are passed along its links, and then
+// the next node gets a turn, until everything has stopped or nothing
+// can make any more progress.
+//
+// A day 7 style feedback loop is just:
+//
+//     let mut network = Network::ring(vec![program; 5]);
+//     network.push_input(0, 0);
+//     network.run()?;
+
+use super::{
+    get_next_output, has_input, push_input, run_program, step, HaltStatus, IntcodeError, Program,
+};
+use log::trace;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Link {
+    // Everything `from` outputs goes to `to`
+    Pipe { from: usize, to: usize },
+    // Everything `from` outputs goes to each of `to`
+    Broadcast { from: usize, to: Vec<usize> },
+    // `from` outputs packets: an address, then `width` more values that
+    // are delivered to the node with that index
+    Addressed { from: usize, width: usize },
+}
+
+impl Link {
+    fn from(&self) -> usize {
+        match self {
+            Link::Pipe { from, .. }
+            | Link::Broadcast { from, .. }
+            | Link::Addressed { from, .. } => *from,
+        }
+    }
+}
+
+// A packet sent to an address that isn't one of the nodes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    pub from: usize,
+    pub address: i64,
+    pub payload: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkStatus {
+    // Every node has stopped
+    Terminated,
+    // Some nodes are waiting for input that nobody is going to send
+    Deadlocked,
+    // With an idle input set: every waiting node was given it, and
+    // still nothing was sent
+    Idle,
+    // Nothing else could run, and `node` (the first, if there's more
+    // than one) had run out of one of its limits
+    Stopped { node: usize, status: HaltStatus },
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    program: Program,
+    // Everything this node has ever output
+    outputs: Vec<i64>,
+    // The start of a packet still being output
+    partial: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Network {
+    nodes: Vec<Node>,
+    links: Vec<Link>,
+    undelivered: Vec<Packet>,
+    // What a node blocked on input gets when the whole network is
+    // stuck, if anything
+    idle_input: Option<i64>,
+}
+
+impl Network {
+    pub fn new() -> Network {
+        Network::default()
+    }
+
+    // Each program feeds the next
+    pub fn pipeline(programs: Vec<Program>) -> Network {
+        let mut network = Network::new();
+        let count = programs.len();
+
+        for program in programs {
+            network.add_node(program);
+        }
+        for i in 1..count {
+            network.link(Link::Pipe { from: i - 1, to: i });
+        }
+
+        network
+    }
+
+    // A pipeline where the last program feeds the first
+    pub fn ring(programs: Vec<Program>) -> Network {
+        let mut network = Network::pipeline(programs);
+        let count = network.nodes.len();
+
+        if count > 0 {
+            network.link(Link::Pipe {
+                from: count - 1,
+                to: 0,
+            });
+        }
+
+        network
+    }
+
+    // Returns the new node's index
+    pub fn add_node(&mut self, program: Program) -> usize {
+        self.nodes.push(Node {
+            program,
+            outputs: Vec::new(),
+            partial: Vec::new(),
+        });
+
+        self.nodes.len() - 1
+    }
+
+    pub fn link(&mut self, link: Link) {
+        self.links.push(link);
+    }
+
+    pub fn set_idle_input(&mut self, value: Option<i64>) {
+        self.idle_input = value;
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn push_input(&mut self, node: usize, value: i64) {
+        push_input(&mut self.nodes[node].program, value);
+    }
+
+    pub fn program(&self, node: usize) -> &Program {
+        &self.nodes[node].program
+    }
+
+    pub fn outputs(&self, node: usize) -> &[i64] {
+        &self.nodes[node].outputs
+    }
+
+    pub fn undelivered(&self) -> &[Packet] {
+        &self.undelivered
+    }
+
+    // The node that faulted, if one did
+    pub fn faulted(&self) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|x| matches!(x.program.halt_status, Some(HaltStatus::Faulted(_))))
+    }
+
+    // Runs until the network stops making progress. A fault in any
+    // node stops everything; `faulted` says which one it was.
+    pub fn run(&mut self) -> Result<NetworkStatus, IntcodeError> {
+        // Whether the idle input has gone out with nothing sent since
+        let mut idle = false;
+
+        loop {
+            let mut ran = false;
+
+            for node in 0..self.nodes.len() {
+                if !self.runnable(node) {
+                    continue;
+                }
+
+                ran = true;
+
+                if self.run_node(node)? {
+                    idle = false;
+                }
+            }
+
+            if ran {
+                continue;
+            }
+
+            if self
+                .nodes
+                .iter()
+                .all(|x| x.program.halt_status == Some(HaltStatus::Terminated))
+            {
+                return Ok(NetworkStatus::Terminated);
+            }
+
+            let statuses = self.nodes.iter().map(|x| x.program.halt_status);
+            if let Some(stopped) = limit_stop(statuses) {
+                return Ok(stopped);
+            }
+
+            match self.idle_input {
+                Some(_) if idle => return Ok(NetworkStatus::Idle),
+                Some(value) => {
+                    trace!("network is idle, sending {}", value);
+                    idle = true;
+
+                    for node in &mut self.nodes {
+                        if node.program.halt_status == Some(HaltStatus::WaitingInput) {
+                            push_input(&mut node.program, value);
+                        }
+                    }
+                }
+                None => return Ok(NetworkStatus::Deadlocked),
+            }
+        }
+    }
+
+    fn runnable(&self, node: usize) -> bool {
+        let program = &self.nodes[node].program;
+
+        match program.halt_status {
+            None => true,
+            Some(HaltStatus::WaitingInput) => has_input(program),
+            Some(_) => false,
+        }
+    }
+
+    // Returns whether the node output anything. Whatever it output
+    // before a fault is still passed along.
+    fn run_node(&mut self, node: usize) -> Result<bool, IntcodeError> {
+        let result = run_program(&mut self.nodes[node].program).map(|_| ());
+
+        let mut outputs = Vec::new();
+
+        while let Some(value) = get_next_output(&mut self.nodes[node].program) {
+            outputs.push(value);
+        }
+
+        trace!("node {} output {:?}", node, outputs);
+
+        for value in &outputs {
+            for (to, value) in self.route(node, *value) {
+                self.push_input(to, value);
+            }
+        }
+
+        result?;
+        Ok(!outputs.is_empty())
+    }
+
+    // Records an output, and works out where it has to go. Both ways of
+    // running the network deliver things themselves.
+    fn route(&mut self, node: usize, value: i64) -> Vec<(usize, i64)> {
+        let mut deliveries = Vec::new();
+
+        self.nodes[node].outputs.push(value);
+
+        for link in self.links.iter().filter(|x| x.from() == node) {
+            match link {
+                Link::Pipe { to, .. } => deliveries.push((*to, value)),
+                Link::Broadcast { to, .. } => deliveries.extend(to.iter().map(|x| (*x, value))),
+                Link::Addressed { width, .. } => {
+                    let partial = &mut self.nodes[node].partial;
+                    partial.push(value);
+
+                    if partial.len() < width + 1 {
+                        continue;
+                    }
+
+                    let payload = partial.split_off(1);
+                    let address = partial.pop().unwrap();
+
+                    if address < 0 || address as usize >= self.nodes.len() {
+                        let packet = Packet {
+                            from: node,
+                            address,
+                            payload,
+                        };
+
+                        trace!("undeliverable packet: {:?}", packet);
+                        self.undelivered.push(packet);
+                    } else {
+                        deliveries.extend(payload.into_iter().map(|x| (address as usize, x)));
+                    }
+                }
+            }
+        }
+
+        deliveries
+    }
+
+    // Same as `run`, but with every node on its own thread, blocking on
+    // a channel for input. Nodes with only one thing feeding them (like
+    // pipelines and rings) see exactly the same inputs as with `run`,
+    // but anything merging several senders can see them interleaved
+    // differently.
+    //
+    // When everything's done, whether that's because it all stopped, got
+    // stuck, or something faulted, every thread is shut down and the
+    // programs are put back where they were. Anything output on the way
+    // down is still recorded and delivered, so no values go missing.
+    pub fn run_threaded(&mut self) -> Result<NetworkStatus, IntcodeError> {
+        let count = self.nodes.len();
+        let (events, received) = mpsc::channel();
+        let mut inputs = Vec::new();
+        let mut threads = Vec::new();
+        // Tells nodes that are still computing to give up
+        let stop = Arc::new(AtomicBool::new(false));
+
+        for (node, x) in self.nodes.iter_mut().enumerate() {
+            let program = mem::replace(&mut x.program, Program::new(vec![]));
+            let (sender, receiver) = mpsc::channel();
+            let events = events.clone();
+            let stop = stop.clone();
+
+            inputs.push(Some(sender));
+            threads.push(thread::spawn(move || {
+                run_node_thread(node, program, receiver, events, &stop)
+            }));
+        }
+
+        drop(events);
+
+        // How many inputs each node has been sent, and what they had all
+        // been given the last time they blocked
+        let mut sent = vec![0; count];
+        let mut stuck = vec![false; count];
+        let mut stopped = vec![false; count];
+        let mut terminated = vec![false; count];
+        let mut statuses = vec![None; count];
+        // Anything sent to a node that had already stopped
+        let mut leftovers = vec![Vec::new(); count];
+        let mut idle = false;
+
+        let result = loop {
+            if terminated.iter().all(|x| *x) {
+                break Ok(NetworkStatus::Terminated);
+            }
+
+            if (0..count).all(|x| stopped[x] || stuck[x]) {
+                if let Some(stopped) = limit_stop(statuses.iter().copied()) {
+                    break Ok(stopped);
+                }
+
+                match self.idle_input {
+                    Some(_) if idle => break Ok(NetworkStatus::Idle),
+                    Some(value) => {
+                        trace!("network is idle, sending {}", value);
+                        idle = true;
+
+                        for node in 0..count {
+                            if stuck[node] {
+                                send(&inputs[node], value, &mut leftovers[node]);
+                                sent[node] += 1;
+                                stuck[node] = false;
+                            }
+                        }
+                    }
+                    None => break Ok(NetworkStatus::Deadlocked),
+                }
+            }
+
+            match received.recv() {
+                Ok(Event::Output(node, value)) => {
+                    idle = false;
+
+                    for (to, value) in self.route(node, value) {
+                        send(&inputs[to], value, &mut leftovers[to]);
+                        sent[to] += 1;
+                        stuck[to] = false;
+                    }
+                }
+                Ok(Event::Blocked(node, given)) => stuck[node] = given == sent[node],
+                Ok(Event::Stopped(_, HaltStatus::Faulted(err))) => break Err(err),
+                Ok(Event::Stopped(node, status)) => {
+                    stopped[node] = true;
+                    statuses[node] = Some(status);
+                    terminated[node] = status == HaltStatus::Terminated;
+                    inputs[node] = None;
+                }
+                // Every thread has gone, which they only do by stopping
+                Err(_) => break Ok(NetworkStatus::Terminated),
+            }
+        };
+
+        // Closing the input channels wakes up anything still waiting
+        stop.store(true, Ordering::Relaxed);
+        inputs.clear();
+
+        let programs = threads
+            .into_iter()
+            .map(|x| x.join().expect("network thread panicked"))
+            .collect::<Vec<Program>>();
+
+        // Outputs nobody had got round to yet. The threads have all
+        // gone, so these are the last of them.
+        for event in received.try_iter() {
+            if let Event::Output(node, value) = event {
+                for (to, value) in self.route(node, value) {
+                    leftovers[to].push(value);
+                }
+            }
+        }
+
+        for (node, mut program) in programs.into_iter().enumerate() {
+            for value in leftovers[node].drain(..) {
+                push_input(&mut program, value);
+            }
+
+            self.nodes[node].program = program;
+        }
+
+        result
+    }
+}
+
+// The first node that ran out of a limit, out of the halt statuses of
+// all of them
+fn limit_stop(statuses: impl Iterator<Item = Option<HaltStatus>>) -> Option<NetworkStatus> {
+    statuses
+        .enumerate()
+        .find_map(|(node, status)| match status? {
+            HaltStatus::Terminated | HaltStatus::WaitingInput | HaltStatus::Faulted(_) => None,
+            status => Some(NetworkStatus::Stopped { node, status }),
+        })
+}
+
+#[derive(Debug)]
+enum Event {
+    Output(usize, i64),
+    // Waiting for input, having been sent this many so far
+    Blocked(usize, usize),
+    // Anything other than waiting for input. The thread exits straight
+    // after.
+    Stopped(usize, HaltStatus),
+}
+
+fn send(input: &Option<Sender<i64>>, value: i64, leftovers: &mut Vec<i64>) {
+    let sent = match input {
+        Some(input) => input.send(value).is_ok(),
+        None => false,
+    };
+
+    if !sent {
+        leftovers.push(value);
+    }
+}
+
+// Steps the program rather than running it, so outputs go out as soon
+// as they're made. Gives up between instructions once `stop` is set,
+// or once nobody's listening for events any more.
+fn run_node_thread(
+    node: usize,
+    mut program: Program,
+    input: Receiver<i64>,
+    events: Sender<Event>,
+    stop: &AtomicBool,
+) -> Program {
+    let mut given = 0;
+
+    while !stop.load(Ordering::Relaxed) {
+        let halt = match step(&mut program) {
+            Ok(step) => {
+                if step.output.is_some() {
+                    let value = get_next_output(&mut program).unwrap();
+
+                    if events.send(Event::Output(node, value)).is_err() {
+                        program.output.push_front(value);
+                        break;
+                    }
+                }
+
+                step.halt
+            }
+            Err(err) => Some(HaltStatus::Faulted(err)),
+        };
+
+        match halt {
+            None => continue,
+            Some(HaltStatus::WaitingInput) => {
+                if events.send(Event::Blocked(node, given)).is_err() {
+                    break;
+                }
+
+                match input.recv() {
+                    Ok(value) => {
+                        given += 1;
+                        push_input(&mut program, value);
+                    }
+                    // Shutting down
+                    Err(_) => return program,
+                }
+            }
+            Some(status) => {
+                let _ = events.send(Event::Stopped(node, status));
+                break;
+            }
+        }
+    }
+
+    // Keep anything that was sent but never read
+    for value in input.try_iter() {
+        push_input(&mut program, value);
+    }
+
+    program
+}
+
+#[test]
+fn pipeline_test() {
+    // Adds one to every input
+    let increment = Program::new(vec![3, 9, 1001, 9, 1, 9, 4, 9, 1105, 1, 0]);
+    let mut network = Network::pipeline(vec![increment; 3]);
+
+    network.push_input(0, 10);
+    network.push_input(0, 20);
+
+    // The last one keeps waiting for more, and nobody's sending any
+    assert_eq!(Ok(NetworkStatus::Deadlocked), network.run());
+    assert_eq!(&[11, 21], network.outputs(0));
+    assert_eq!(&[13, 23], network.outputs(2));
+}
+
+#[test]
+fn limit_test() {
+    // Adds one to every input, but the middle one can only run a few
+    // instructions
+    let increment = Program::new(vec![3, 9, 1001, 9, 1, 9, 4, 9, 1105, 1, 0]);
+    let mut limited = increment.clone();
+    limited.limits.max_instructions = Some(10);
+
+    let mut network = Network::pipeline(vec![increment.clone(), limited, increment]);
+    for x in 0..5 {
+        network.push_input(0, x);
+    }
+    let mut threaded = network.clone();
+
+    let stopped = NetworkStatus::Stopped {
+        node: 1,
+        status: HaltStatus::InstructionLimit,
+    };
+    assert_eq!(Ok(stopped), network.run());
+    assert_eq!(Ok(stopped), threaded.run_threaded());
+    assert_eq!(&[3, 4], network.outputs(2));
+    assert_eq!(network.outputs(2), threaded.outputs(2));
+}
+
+#[test]
+fn broadcast_and_fault_test() {
+    // Outputs its input, then stops
+    let echo = Program::new(vec![3, 5, 4, 5, 99, 0]);
+    let mut network = Network::new();
+
+    for _ in 0..3 {
+        network.add_node(echo.clone());
+    }
+    network.add_node(Program::new(vec![3, 0, 42]));
+    network.link(Link::Broadcast {
+        from: 0,
+        to: vec![1, 2],
+    });
+    network.push_input(0, 7);
+
+    network.push_input(3, 1);
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode {
+            address: 2,
+            opcode: 42
+        }),
+        network.run()
+    );
+    assert_eq!(Some(3), network.faulted());
+
+    network.nodes.pop();
+    assert_eq!(Ok(NetworkStatus::Terminated), network.run());
+    assert_eq!(&[7], network.outputs(1));
+    assert_eq!(&[7], network.outputs(2));
+}
+
+#[test]
+fn addressed_test() {
+    use super::asm::assemble;
+
+    // Node 0 sends each input to node 1 as a two-value packet, and to
+    // address 99, which isn't anyone. Node 1 adds up each packet it
+    // gets and sends the sum to 99 as well. Both ignore the idle -1.
+    let sender = assemble(
+        "
+        start:  IN [value]
+                EQ [value], #-1, [idle]
+                JNZ [idle], #start
+                OUT #1
+                OUT [value]
+                OUT #100
+                OUT #99
+                OUT [value]
+                OUT #0
+                JNZ #1, #start
+        value:  .data 0
+        idle:   .data 0
+        ",
+    )
+    .unwrap();
+    let adder = assemble(
+        "
+        start:  IN [x]
+                EQ [x], #-1, [idle]
+                JNZ [idle], #start
+                IN [y]
+                ADD [x], [y], [x]
+                OUT #99
+                OUT [x]
+                JNZ #1, #start
+        x:      .data 0
+        y:      .data 0
+        idle:   .data 0
+        ",
+    )
+    .unwrap();
+
+    let mut network = Network::new();
+    network.add_node(Program::new(sender));
+    network.add_node(Program::new(adder));
+    network.link(Link::Addressed { from: 0, width: 2 });
+    network.link(Link::Addressed { from: 1, width: 1 });
+    network.set_idle_input(Some(-1));
+    network.push_input(0, 5);
+
+    assert_eq!(Ok(NetworkStatus::Idle), network.run());
+    assert_eq!(&[1, 5, 100, 99, 5, 0], network.outputs(0));
+    assert_eq!(&[99, 105], network.outputs(1));
+    assert_eq!(
+        &[
+            Packet {
+                from: 0,
+                address: 99,
+                payload: vec![5, 0]
+            },
+            Packet {
+                from: 1,
+                address: 99,
+                payload: vec![105]
+            }
+        ],
+        network.undelivered()
+    );
+}
+
+#[test]
+fn threaded_test() {
+    // Adds one to every input
+    let increment = Program::new(vec![3, 9, 1001, 9, 1, 9, 4, 9, 1105, 1, 0]);
+    let mut network = Network::pipeline(vec![increment; 4]);
+
+    for x in 0..100 {
+        network.push_input(0, x * 10);
+    }
+
+    let mut threaded = network.clone();
+
+    assert_eq!(network.run(), threaded.run_threaded());
+
+    for node in 0..network.len() {
+        assert_eq!(network.outputs(node), threaded.outputs(node));
+        assert_eq!(
+            network.program(node).state.cells(),
+            threaded.program(node).state.cells()
+        );
+    }
+
+    // Outputs its input, then stops, except for the last one, which
+    // faults. Everything else still gets shut down.
+    let echo = Program::new(vec![3, 5, 4, 5, 99, 0]);
+    let mut network = Network::ring(vec![echo.clone(), echo, Program::new(vec![3, 0, 42])]);
+    network.push_input(0, 1);
+
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode {
+            address: 2,
+            opcode: 42
+        }),
+        network.run_threaded()
+    );
+    assert_eq!(Some(2), network.faulted());
+    assert_eq!(Some(HaltStatus::Terminated), network.program(0).halt_status);
+}
+
+#[test]
+fn threaded_fault_stops_busy_nodes_test() {
+    // One node loops forever, another outputs forever, and the last one
+    // faults straight away. Neither of the busy ones ever blocks, so
+    // they have to be told to stop.
+    let mut network = Network::new();
+    network.add_node(Program::new(vec![1105, 1, 0]));
+    network.add_node(Program::new(vec![104, 1, 1105, 1, 0]));
+    network.add_node(Program::new(vec![42]));
+
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode {
+            address: 0,
+            opcode: 42
+        }),
+        network.run_threaded()
+    );
+    assert_eq!(Some(2), network.faulted());
+    // Put back part way through, ready to carry on
+    assert_eq!(None, network.program(0).halt_status);
+
+    // Every OUT the busy one ran is either recorded or still queued
+    let program = network.program(1);
+    assert_eq!(
+        (program.executed as usize).div_ceil(2),
+        network.outputs(1).len() + program.output.len()
+    );
+}
+
+#[test]
+fn threaded_fault_matches_test() {
+    // Outputs two values and stops, the next adds them up, passes the
+    // sum on and then faults, and the last just reads
+    let mut network = Network::pipeline(vec![
+        Program::new(vec![104, 1, 104, 2, 99]),
+        Program::new(vec![3, 11, 3, 12, 1, 11, 12, 11, 4, 11, 42, 0, 0]),
+        Program::new(vec![3, 5, 3, 5, 99, 0]),
+    ]);
+    let mut threaded = network.clone();
+
+    let fault = Err(IntcodeError::UnknownOpcode {
+        address: 10,
+        opcode: 42,
+    });
+    assert_eq!(fault, network.run());
+    assert_eq!(fault, threaded.run_threaded());
+    assert_eq!(network.faulted(), threaded.faulted());
+
+    for node in 0..network.len() {
+        assert_eq!(network.outputs(node), threaded.outputs(node));
+    }
+    assert_eq!(&[3], network.outputs(1));
+}
@@ -0,0 +1,237 @@
+// Counts where a program spends its time. Attach it as an observer:
+//
+//     let mut profiler = Profiler::new();
+//     run_program_with(&mut program, &mut profiler)?;
+//     println!("{}", profiler.report(&image));
+//
+// Loops are found by their back edges: any jump taken to an address at
+// or before itself.
+
+use super::dialect::Parameter;
+use super::disasm::{self, Entry};
+use super::{get_state, HaltStatus, Observer, OpCode, ParameterMode, Program, StepResult};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// How many of each thing to show in the report
+const TOP: usize = 10;
+
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    pub executed: HashMap<i64, u64>,
+    pub opcodes: HashMap<&'static str, u64>,
+    // (start, end) of each loop: the jump target, and the jump itself
+    pub loops: HashMap<(i64, i64), u64>,
+    pub reads: HashMap<i64, u64>,
+    pub writes: HashMap<i64, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.executed.values().sum()
+    }
+
+    // Every word of `image` that was never part of an instruction that
+    // ran, as inclusive ranges
+    pub fn unexecuted(&self, image: &[i64]) -> Vec<(i64, i64)> {
+        let mut covered = vec![false; image.len()];
+
+        for address in self.executed.keys() {
+            if let Some(Entry::Instruction { parameters, .. }) = disasm::decode(image, *address) {
+                for word in *address..=*address + parameters.len() as i64 {
+                    covered[word as usize] = true;
+                }
+            }
+        }
+
+        let mut ranges: Vec<(i64, i64)> = Vec::new();
+
+        for address in (0..image.len()).filter(|x| !covered[*x]) {
+            let address = address as i64;
+
+            match ranges.last_mut() {
+                Some((_, end)) if *end == address - 1 => *end = address,
+                _ => ranges.push((address, address)),
+            }
+        }
+
+        ranges
+    }
+
+    // A text summary, followed by the program's disassembly with each
+    // line annotated with how often it ran, or was read and written
+    pub fn report(&self, image: &[i64]) -> String {
+        let mut report = String::new();
+
+        writeln!(report, "Instructions executed: {}", self.instructions()).unwrap();
+
+        writeln!(report, "\nBy opcode:").unwrap();
+        for (mnemonic, count) in sorted(&self.opcodes, usize::MAX) {
+            writeln!(report, "{:>12}  {}", count, mnemonic).unwrap();
+        }
+
+        writeln!(report, "\nHottest addresses:").unwrap();
+        for (address, count) in sorted(&self.executed, TOP) {
+            writeln!(report, "{:>12}  {}", count, address).unwrap();
+        }
+
+        writeln!(report, "\nHot loops:").unwrap();
+        for ((start, end), count) in sorted(&self.loops, TOP) {
+            writeln!(report, "{:>12}  {}..{}", count, start, end).unwrap();
+        }
+
+        writeln!(report, "\nMost read:").unwrap();
+        for (address, count) in sorted(&self.reads, TOP) {
+            writeln!(report, "{:>12}  {}", count, address).unwrap();
+        }
+
+        writeln!(report, "\nMost written:").unwrap();
+        for (address, count) in sorted(&self.writes, TOP) {
+            writeln!(report, "{:>12}  {}", count, address).unwrap();
+        }
+
+        writeln!(report, "\nNever executed:").unwrap();
+        for (start, end) in self.unexecuted(image) {
+            match end - start {
+                0 => writeln!(report, "{:>12}  {}", "", start).unwrap(),
+                _ => writeln!(report, "{:>12}  {}..{}", "", start, end).unwrap(),
+            }
+        }
+
+        writeln!(report, "\n{:>10} {:>8} {:>8}", "runs", "reads", "writes").unwrap();
+        for entry in disasm::disassemble(image) {
+            let address = entry.address();
+            let count = |counts: &HashMap<i64, u64>| match counts.get(&address) {
+                Some(count) => count.to_string(),
+                None => "".to_owned(),
+            };
+
+            writeln!(
+                report,
+                "{:>10} {:>8} {:>8} {}",
+                count(&self.executed),
+                count(&self.reads),
+                count(&self.writes),
+                entry
+            )
+            .unwrap();
+        }
+
+        report
+    }
+}
+
+impl Observer for Profiler {
+    fn before_step(&mut self, program: &Program, address: i64, opcode: OpCode) {
+        let parameters = opcode.parameters().into_iter().zip(opcode.modes());
+
+        for (i, (parameter, mode)) in parameters.enumerate() {
+            // Where they write isn't a read
+            if parameter == Parameter::Write {
+                continue;
+            }
+
+            // Anything out of range is left for the instruction itself
+            // to fault on
+            let word = match address.checked_add(i as i64 + 1) {
+                Some(x) => get_state(program, x, ParameterMode::Immediate),
+                None => continue,
+            };
+
+            let read = match (mode, word) {
+                (ParameterMode::Position, Ok(word)) => word,
+                (ParameterMode::Relative, Ok(word)) => {
+                    match program.relative_base.checked_add(word) {
+                        Some(x) => x,
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            *self.reads.entry(read).or_insert(0) += 1;
+        }
+    }
+
+    fn after_step(&mut self, _program: &Program, step: &StepResult) {
+        // An input instruction that's waiting hasn't run yet, but a
+        // halt has
+        if step.halt.is_none() || step.halt == Some(HaltStatus::Terminated) {
+            *self.executed.entry(step.address).or_insert(0) += 1;
+            *self
+                .opcodes
+                .entry(disasm::mnemonic(step.opcode))
+                .or_insert(0) += 1;
+        }
+
+        if let Some(write) = step.write {
+            *self.writes.entry(write.address).or_insert(0) += 1;
+        }
+
+        // Only a jump moves the pointer backwards
+        if step.halt.is_none() && step.next_pointer <= step.address {
+            *self
+                .loops
+                .entry((step.next_pointer, step.address))
+                .or_insert(0) += 1;
+        }
+    }
+}
+
+// The biggest `count` values, biggest first, ties in key order
+fn sorted<K: Ord + Copy>(counts: &HashMap<K, u64>, count: usize) -> Vec<(K, u64)> {
+    let mut sorted = counts.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    sorted.truncate(count);
+    sorted
+}
+
+#[test]
+fn profiler_test() {
+    use super::{push_input, run_program_with};
+
+    // Counts down from its input, outputting as it goes, then stops.
+    // The last word is never reached.
+    let image = vec![
+        109, 20, 203, 0, 4, 20, 1001, 20, -1, 20, 1005, 20, 4, 99, 42,
+    ];
+    let mut program = Program::new(image.clone());
+    let mut profiler = Profiler::new();
+
+    push_input(&mut program, 3);
+    run_program_with(&mut program, &mut profiler).unwrap();
+
+    assert_eq!(3, profiler.executed[&4]);
+    assert_eq!(1, profiler.executed[&13]);
+    assert_eq!(3, profiler.opcodes["JNZ"]);
+    // Taken twice, falling through the third time
+    assert_eq!(2, profiler.loops[&(4, 10)]);
+    // Read by each OUT, ADD and JNZ
+    assert_eq!(9, profiler.reads[&20]);
+    // Written once by IN and three times by ADD
+    assert_eq!(4, profiler.writes[&20]);
+    assert_eq!(vec![(14, 14)], profiler.unexecuted(&image));
+
+    let report = profiler.report(&image);
+
+    assert!(report.contains("Instructions executed: 12\n"));
+    assert!(report.contains("           2  4..10\n"));
+    assert!(report.contains("         3                      10: JNZ [20], #4\n"));
+    assert!(report.contains("                                14: .data 42\n"));
+
+    // A relative read off the end of the address space is the
+    // program's fault, not the profiler's
+    let mut program = Program::new(vec![109, i64::MAX, 204, 1, 99]);
+    let mut profiler = Profiler::new();
+
+    assert_eq!(
+        Err(super::IntcodeError::Overflow { address: 2 }),
+        run_program_with(&mut program, &mut profiler).map(|_| ())
+    );
+    assert!(profiler.reads.is_empty());
+}
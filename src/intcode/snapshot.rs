@@ -0,0 +1,385 @@
+// Saves a whole machine as text, and reads it back. The format is one
+// field per line:
+//
+//     intcode-snapshot 1
+//     pointer 25
+//     relative_base 2000
+//     halt_status waiting-input
+//     input 1,2
+//     output
+//     executed 1234
+//     memory 0 3,15,3,16,99
+//     memory 1099511627776 7
+//
+// Memory is written as runs of consecutive cells, each starting at the
+// given address, so sparse programs stay small. Programs using wrapping
+// arithmetic get an `arithmetic wrapping` line too, and any limits that
+// are set get a line each (`max_instructions 5000`).
+//
+// A deadline is a point in time in this process, and a dialect's
+// instructions are code, so programs with either can't be saved.
+
+use super::arithmetic::Arithmetic;
+use super::memory::FlatMemory;
+use super::{HaltStatus, IntcodeError, Program};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+
+pub const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    UnsupportedVersion(i64),
+    // 1-based line in the snapshot
+    Parse { line: usize, message: String },
+    // Something about the program that the format can't hold
+    Unsaveable(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "couldn't read snapshot: {}", err),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {} isn't supported (expected {})",
+                version, VERSION
+            ),
+            SnapshotError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            SnapshotError::Unsaveable(reason) => write!(f, "can't save {}", reason),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
+
+pub fn save(program: &Program) -> Result<String, SnapshotError> {
+    if program.dialect().is_some() {
+        return Err(SnapshotError::Unsaveable("a program with a dialect"));
+    }
+    if program.limits.deadline.is_some() {
+        return Err(SnapshotError::Unsaveable("a program with a deadline"));
+    }
+
+    let mut lines = vec![
+        format!("intcode-snapshot {}", VERSION),
+        format!("pointer {}", program.pointer),
+        format!("relative_base {}", program.relative_base),
+        format!("halt_status {}", halt_status_to_string(program.halt_status)),
+        format!("input {}", join(program.input.iter())),
+        format!("output {}", join(program.output.iter())),
+        format!("executed {}", program.executed),
+    ]
+    .into_iter()
+    .map(|x| x.trim_end().to_owned())
+    .collect::<Vec<String>>();
+
+    if program.arithmetic == Arithmetic::Wrapping {
+        lines.push("arithmetic wrapping".to_owned());
+    }
+
+    let limits = &program.limits;
+    let limits = [
+        ("max_instructions", limits.max_instructions),
+        (
+            "max_memory_cells",
+            limits.max_memory_cells.map(|x| x as u64),
+        ),
+        ("max_output", limits.max_output.map(|x| x as u64)),
+    ];
+
+    for (name, limit) in limits.iter() {
+        if let Some(limit) = limit {
+            lines.push(format!("{} {}", name, limit));
+        }
+    }
+
+    let mut run: Vec<i64> = Vec::new();
+    let mut run_start = 0;
+
+    for (address, value) in program.state.cells() {
+        if !run.is_empty() && address != run_start + run.len() as i64 {
+            lines.push(format!("memory {} {}", run_start, join(run.iter())));
+            run.clear();
+        }
+        if run.is_empty() {
+            run_start = address;
+        }
+        run.push(value);
+    }
+
+    if !run.is_empty() {
+        lines.push(format!("memory {} {}", run_start, join(run.iter())));
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+pub fn load(text: &str) -> Result<Program, SnapshotError> {
+    let mut lines = text.lines().enumerate().map(|(i, x)| (i + 1, x.trim()));
+
+    match lines.next() {
+        Some((line, header)) => match header.strip_prefix("intcode-snapshot ") {
+            Some(version) => match parse_number(line, version)? {
+                x if x == i64::from(VERSION) => (),
+                x => return Err(SnapshotError::UnsupportedVersion(x)),
+            },
+            None => return Err(parse_error(line, "not an Intcode snapshot")),
+        },
+        None => return Err(parse_error(1, "empty snapshot")),
+    }
+
+    let mut program = Program::with_memory(Box::new(FlatMemory::default()));
+
+    for (line, text) in lines.filter(|(_, x)| !x.is_empty()) {
+        let (field, value) = match text.find(' ') {
+            Some(i) => (&text[..i], text[i + 1..].trim()),
+            None => (text, ""),
+        };
+
+        match field {
+            "pointer" => program.pointer = parse_number(line, value)?,
+            "relative_base" => program.relative_base = parse_number(line, value)?,
+            "halt_status" => program.halt_status = parse_halt_status(line, value)?,
+            "input" => program.input = parse_list(line, value)?.into_iter().collect(),
+            "output" => program.output = parse_list(line, value)?.into_iter().collect(),
+            "executed" => program.executed = parse_count(line, value)?,
+            "max_instructions" => program.limits.max_instructions = Some(parse_count(line, value)?),
+            "max_memory_cells" => {
+                program.limits.max_memory_cells = Some(parse_count(line, value)? as usize)
+            }
+            "max_output" => program.limits.max_output = Some(parse_count(line, value)? as usize),
+            "arithmetic" => {
+                program.arithmetic = match value {
+                    "checked" => Arithmetic::Checked,
+                    "wrapping" => Arithmetic::Wrapping,
+                    x => return Err(parse_error(line, &format!("bad arithmetic: {}", x))),
+                }
+            }
+            "memory" => {
+                let (start, values) = match value.find(' ') {
+                    Some(i) => (&value[..i], &value[i + 1..]),
+                    None => return Err(parse_error(line, "memory needs an address and values")),
+                };
+                let start = parse_number(line, start)?;
+
+                for (i, x) in parse_list(line, values)?.into_iter().enumerate() {
+                    let address = start
+                        .checked_add(i as i64)
+                        .ok_or_else(|| parse_error(line, "memory runs past the last address"))?;
+                    program.state.set(address, x);
+                }
+            }
+            x => return Err(parse_error(line, &format!("unknown field: {}", x))),
+        }
+    }
+
+    Ok(program)
+}
+
+pub fn save_to_file(program: &Program, filepath: &str) -> Result<(), SnapshotError> {
+    fs::write(filepath, save(program)?)?;
+    Ok(())
+}
+
+pub fn load_from_file(filepath: &str) -> Result<Program, SnapshotError> {
+    load(&fs::read_to_string(filepath)?)
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a i64>) -> String {
+    values
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn parse_error(line: usize, message: &str) -> SnapshotError {
+    SnapshotError::Parse {
+        line,
+        message: message.to_owned(),
+    }
+}
+
+fn parse_number(line: usize, text: &str) -> Result<i64, SnapshotError> {
+    text.trim()
+        .parse::<i64>()
+        .map_err(|_| parse_error(line, &format!("bad number: {}", text)))
+}
+
+fn parse_count(line: usize, text: &str) -> Result<u64, SnapshotError> {
+    text.trim()
+        .parse::<u64>()
+        .map_err(|_| parse_error(line, &format!("bad count: {}", text)))
+}
+
+fn parse_list(line: usize, text: &str) -> Result<Vec<i64>, SnapshotError> {
+    if text.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    text.split(',').map(|x| parse_number(line, x)).collect()
+}
+
+pub(super) fn halt_status_to_string(status: Option<HaltStatus>) -> String {
+    match status {
+        None => "running".to_owned(),
+        Some(HaltStatus::Terminated) => "terminated".to_owned(),
+        Some(HaltStatus::WaitingInput) => "waiting-input".to_owned(),
+        Some(HaltStatus::InstructionLimit) => "instruction-limit".to_owned(),
+        Some(HaltStatus::MemoryLimit) => "memory-limit".to_owned(),
+        Some(HaltStatus::OutputLimit) => "output-limit".to_owned(),
+        Some(HaltStatus::DeadlineExceeded) => "deadline-exceeded".to_owned(),
+        Some(HaltStatus::Faulted(err)) => {
+            let (kind, numbers) = match err {
+                IntcodeError::UnknownOpcode { address, opcode } => {
+                    ("unknown-opcode", vec![address, opcode])
+                }
+                IntcodeError::InvalidParameterMode { address, mode } => {
+                    ("invalid-parameter-mode", vec![address, mode])
+                }
+                IntcodeError::NegativeAddress { address } => ("negative-address", vec![address]),
+                IntcodeError::ImmediateWrite { address } => ("immediate-write", vec![address]),
+                IntcodeError::Overflow { address } => ("overflow", vec![address]),
+                IntcodeError::MissingInput { address } => ("missing-input", vec![address]),
+                IntcodeError::DivideByZero { address } => ("divide-by-zero", vec![address]),
+            };
+
+            format!("faulted {} {}", kind, join(numbers.iter()))
+        }
+    }
+}
+
+pub(super) fn parse_halt_status(
+    line: usize,
+    text: &str,
+) -> Result<Option<HaltStatus>, SnapshotError> {
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+
+    let status = match words.as_slice() {
+        ["running"] => None,
+        ["terminated"] => Some(HaltStatus::Terminated),
+        ["waiting-input"] => Some(HaltStatus::WaitingInput),
+        ["instruction-limit"] => Some(HaltStatus::InstructionLimit),
+        ["memory-limit"] => Some(HaltStatus::MemoryLimit),
+        ["output-limit"] => Some(HaltStatus::OutputLimit),
+        ["deadline-exceeded"] => Some(HaltStatus::DeadlineExceeded),
+        ["faulted", kind, numbers] => {
+            let numbers = parse_list(line, numbers)?;
+
+            let err = match (*kind, numbers.as_slice()) {
+                ("unknown-opcode", [address, opcode]) => IntcodeError::UnknownOpcode {
+                    address: *address,
+                    opcode: *opcode,
+                },
+                ("invalid-parameter-mode", [address, mode]) => IntcodeError::InvalidParameterMode {
+                    address: *address,
+                    mode: *mode,
+                },
+                ("negative-address", [address]) => {
+                    IntcodeError::NegativeAddress { address: *address }
+                }
+                ("immediate-write", [address]) => {
+                    IntcodeError::ImmediateWrite { address: *address }
+                }
+                ("overflow", [address]) => IntcodeError::Overflow { address: *address },
+                ("missing-input", [address]) => IntcodeError::MissingInput { address: *address },
+                ("divide-by-zero", [address]) => IntcodeError::DivideByZero { address: *address },
+                _ => return Err(parse_error(line, &format!("bad fault: {}", text))),
+            };
+
+            Some(HaltStatus::Faulted(err))
+        }
+        _ => return Err(parse_error(line, &format!("bad halt status: {}", text))),
+    };
+
+    Ok(status)
+}
+
+#[test]
+fn round_trip_test() {
+    use super::memory::FLAT_LIMIT;
+    use super::{get_next_output, push_input, run_program, set_state};
+
+    // Doubles every input it gets, forever
+    let mut program = Program::new(vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0]);
+    set_state(&mut program, FLAT_LIMIT * 4, -7);
+    push_input(&mut program, 21);
+    run_program(&mut program).unwrap();
+    push_input(&mut program, 5);
+
+    program.limits.max_instructions = Some(5_000);
+    program.limits.max_output = Some(3);
+
+    let saved = save(&program).unwrap();
+    assert!(saved.contains(&format!("memory {} -7", FLAT_LIMIT * 4)));
+
+    let mut restored = load(&saved).unwrap();
+
+    assert_eq!(saved, save(&restored).unwrap());
+    assert_eq!(program.pointer, restored.pointer);
+    assert_eq!(program.executed, restored.executed);
+    assert_eq!(program.limits, restored.limits);
+    assert_eq!(program.state.cells(), restored.state.cells());
+
+    // And it carries on where it left off
+    assert_eq!(Some(42), get_next_output(&mut restored));
+    run_program(&mut restored).unwrap();
+    assert_eq!(Some(10), get_next_output(&mut restored));
+    assert_eq!(Some(HaltStatus::WaitingInput), restored.halt_status);
+}
+
+#[test]
+fn fault_round_trip_test() {
+    let mut program = Program::new(vec![301, 0, 0, 0, 99]);
+    super::run_program(&mut program).unwrap_err();
+
+    let restored = load(&save(&program).unwrap()).unwrap();
+
+    assert_eq!(program.halt_status, restored.halt_status);
+}
+
+#[test]
+fn load_error_test() {
+    assert!(matches!(
+        load("intcode-snapshot 2\n"),
+        Err(SnapshotError::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        load("intcode-snapshot -1\n"),
+        Err(SnapshotError::UnsupportedVersion(-1))
+    ));
+    assert!(matches!(
+        load("hello\n"),
+        Err(SnapshotError::Parse { line: 1, .. })
+    ));
+    assert!(matches!(
+        load("intcode-snapshot 1\npointer 0\nmemory 0 1,x,3\n"),
+        Err(SnapshotError::Parse { line: 3, .. })
+    ));
+    assert!(matches!(
+        load("intcode-snapshot 1\nmemory 9223372036854775807 1,2\n"),
+        Err(SnapshotError::Parse { line: 2, .. })
+    ));
+}
+
+#[test]
+fn unsaveable_test() {
+    use super::dialect::Dialect;
+    use std::time::Instant;
+
+    let program = Program::with_dialect(vec![99], Dialect::standard());
+    assert!(matches!(save(&program), Err(SnapshotError::Unsaveable(_))));
+
+    let mut program = Program::new(vec![99]);
+    program.limits.deadline = Some(Instant::now());
+    assert!(matches!(save(&program), Err(SnapshotError::Unsaveable(_))));
+}
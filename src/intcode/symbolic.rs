@@ -0,0 +1,761 @@
+// Symbolic execution: some memory cells or inputs are unknowns, and
+// running the program builds up expressions in them instead of
+// numbers. A jump on an unknown follows both ways, each remembering
+// which way it went as a path condition. Then `solve` looks for values
+// of the unknowns that give a wanted output or memory value, using a
+// solver that can only handle linear expressions. That's all day 2
+// needs:
+//
+//     let mut program = SymbolicProgram::new(&image);
+//     program.symbolic_cell(1, "noun", 0..=99);
+//     program.symbolic_cell(2, "verb", 0..=99);
+//     program.solve(Target::Memory(0), 19_690_720, 10_000)?
+//
+// Reading through an unknown address gives a value we know nothing
+// about, which is fine as long as nothing we care about depends on it.
+// Writing through one, or jumping to one, gives up.
+
+use super::{parse_opcode, HaltStatus, IntcodeError, OpCode, ParameterMode};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(i64),
+    // Index into `SymbolicProgram::symbols`
+    Symbol(usize),
+    // Read through an address that depended on a symbol
+    Opaque,
+    Add(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+}
+
+// constant + sum of coefficient * symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linear {
+    pub constant: i64,
+    pub coefficients: BTreeMap<usize, i64>,
+}
+
+impl Expr {
+    fn add(x: Expr, y: Expr) -> Option<Expr> {
+        Some(match (x, y) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const(x.checked_add(y)?),
+            (Expr::Const(0), y) => y,
+            (x, Expr::Const(0)) => x,
+            (x, y) => Expr::Add(Box::new(x), Box::new(y)),
+        })
+    }
+
+    fn multiply(x: Expr, y: Expr) -> Option<Expr> {
+        Some(match (x, y) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const(x.checked_mul(y)?),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), y) => y,
+            (x, Expr::Const(1)) => x,
+            (x, y) => Expr::Multiply(Box::new(x), Box::new(y)),
+        })
+    }
+
+    fn compare(x: Expr, y: Expr, equals: bool) -> Expr {
+        match (x, y, equals) {
+            (Expr::Const(x), Expr::Const(y), true) => Expr::Const((x == y) as i64),
+            (Expr::Const(x), Expr::Const(y), false) => Expr::Const((x < y) as i64),
+            (x, y, true) => Expr::Equals(Box::new(x), Box::new(y)),
+            (x, y, false) => Expr::LessThan(Box::new(x), Box::new(y)),
+        }
+    }
+
+    pub fn constant(&self) -> Option<i64> {
+        match self {
+            Expr::Const(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    // Every symbol it mentions
+    fn symbols(&self, found: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Const(_) | Expr::Opaque => (),
+            Expr::Symbol(x) => {
+                found.insert(*x);
+            }
+            Expr::Add(x, y) | Expr::Multiply(x, y) | Expr::LessThan(x, y) | Expr::Equals(x, y) => {
+                x.symbols(found);
+                y.symbols(found);
+            }
+        }
+    }
+
+    // The value given values for all the symbols, if it has one
+    pub fn eval(&self, values: &[i64]) -> Option<i64> {
+        match self {
+            Expr::Const(x) => Some(*x),
+            Expr::Symbol(x) => values.get(*x).copied(),
+            Expr::Opaque => None,
+            Expr::Add(x, y) => x.eval(values)?.checked_add(y.eval(values)?),
+            Expr::Multiply(x, y) => x.eval(values)?.checked_mul(y.eval(values)?),
+            Expr::LessThan(x, y) => Some((x.eval(values)? < y.eval(values)?) as i64),
+            Expr::Equals(x, y) => Some((x.eval(values)? == y.eval(values)?) as i64),
+        }
+    }
+
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(x) => Some(Linear {
+                constant: *x,
+                coefficients: BTreeMap::new(),
+            }),
+            Expr::Symbol(x) => Some(Linear {
+                constant: 0,
+                coefficients: vec![(*x, 1)].into_iter().collect(),
+            }),
+            Expr::Add(x, y) => {
+                let (mut x, y) = (x.linear()?, y.linear()?);
+
+                x.constant = x.constant.checked_add(y.constant)?;
+                for (symbol, coefficient) in y.coefficients {
+                    let sum = x.coefficients.entry(symbol).or_insert(0);
+                    *sum = sum.checked_add(coefficient)?;
+                }
+
+                Some(x)
+            }
+            Expr::Multiply(x, y) => {
+                let (x, y) = (x.linear()?, y.linear()?);
+                let (scale, mut x) = match (x.coefficients.is_empty(), y.coefficients.is_empty()) {
+                    (true, _) => (x.constant, y),
+                    (_, true) => (y.constant, x),
+                    _ => return None,
+                };
+
+                x.constant = x.constant.checked_mul(scale)?;
+                for coefficient in x.coefficients.values_mut() {
+                    *coefficient = coefficient.checked_mul(scale)?;
+                }
+
+                Some(x)
+            }
+            _ => None,
+        }
+    }
+}
+
+// One of the unknowns, and the values it's allowed to take
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub range: RangeInclusive<i64>,
+}
+
+// Which way a jump went: `expr` was non-zero, or it wasn't
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub expr: Expr,
+    pub nonzero: bool,
+}
+
+impl Condition {
+    fn holds(&self, values: &[i64]) -> bool {
+        self.expr
+            .eval(values)
+            .is_some_and(|x| (x != 0) == self.nonzero)
+    }
+}
+
+// One way through the program, and where it ended up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub conditions: Vec<Condition>,
+    pub memory: HashMap<i64, Expr>,
+    pub outputs: Vec<Expr>,
+    pub halt: HaltStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Memory(i64),
+    // The nth output
+    Output(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolicError {
+    // The instruction at `address` isn't a known number
+    SymbolicOpcode { address: i64 },
+    // The instruction at `address` writes or jumps somewhere unknown,
+    // or moves the relative base by an unknown amount
+    SymbolicAddress { address: i64 },
+    // All the paths together ran more instructions than allowed
+    StepLimit,
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolicError::SymbolicOpcode { address } => {
+                write!(f, "symbolic opcode at address {}", address)
+            }
+            SymbolicError::SymbolicAddress { address } => {
+                write!(f, "symbolic address used at address {}", address)
+            }
+            SymbolicError::StepLimit => write!(f, "too many instructions"),
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+#[derive(Debug, Clone)]
+pub struct SymbolicProgram {
+    pub memory: HashMap<i64, Expr>,
+    pub pointer: i64,
+    pub relative_base: i64,
+    pub input: VecDeque<Expr>,
+    pub outputs: Vec<Expr>,
+    pub symbols: Vec<Symbol>,
+    conditions: Vec<Condition>,
+}
+
+impl SymbolicProgram {
+    pub fn new(image: &[i64]) -> SymbolicProgram {
+        SymbolicProgram {
+            memory: (0..).zip(image.iter().map(|x| Expr::Const(*x))).collect(),
+            pointer: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            outputs: Vec::new(),
+            symbols: Vec::new(),
+            conditions: Vec::new(),
+        }
+    }
+
+    fn symbol(&mut self, name: &str, range: RangeInclusive<i64>) -> Expr {
+        self.symbols.push(Symbol {
+            name: name.to_owned(),
+            range,
+        });
+
+        Expr::Symbol(self.symbols.len() - 1)
+    }
+
+    pub fn symbolic_cell(&mut self, address: i64, name: &str, range: RangeInclusive<i64>) {
+        let symbol = self.symbol(name, range);
+        self.memory.insert(address, symbol);
+    }
+
+    pub fn symbolic_input(&mut self, name: &str, range: RangeInclusive<i64>) {
+        let symbol = self.symbol(name, range);
+        self.input.push_back(symbol);
+    }
+
+    pub fn push_input(&mut self, input: i64) {
+        self.input.push_back(Expr::Const(input));
+    }
+
+    // Every way through the program, until each path stops, faults or
+    // needs more input
+    pub fn explore(&self, max_steps: u64) -> Result<Vec<Path>, SymbolicError> {
+        let mut paths = Vec::new();
+
+        self.walk(max_steps, |path| {
+            paths.push(path);
+            None::<()>
+        })?;
+
+        Ok(paths)
+    }
+
+    // Explores, handing each path to `finished` as soon as it stops,
+    // until `finished` gives something back
+    fn walk<T>(
+        &self,
+        max_steps: u64,
+        mut finished: impl FnMut(Path) -> Option<T>,
+    ) -> Result<Option<T>, SymbolicError> {
+        let mut pending = vec![self.clone()];
+        let mut steps = 0;
+
+        while let Some(mut machine) = pending.pop() {
+            let halt = loop {
+                steps += 1;
+                if steps > max_steps {
+                    return Err(SymbolicError::StepLimit);
+                }
+
+                match machine.step() {
+                    Ok(Step::Next) => (),
+                    Ok(Step::Fork(other)) => pending.push(*other),
+                    Ok(Step::Halt(status)) => break status,
+                    Err(Fault::Intcode(err)) => break HaltStatus::Faulted(err),
+                    Err(Fault::Symbolic(err)) => return Err(err),
+                }
+            };
+
+            let found = finished(Path {
+                conditions: machine.conditions,
+                memory: machine.memory,
+                outputs: machine.outputs,
+                halt,
+            });
+
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Values for the symbols, in the order they were added, that make
+    // `target` come out as `value` on some path that terminates. Each
+    // path is checked as soon as it's done, so paths that never finish
+    // only matter if nothing before them works.
+    pub fn solve(
+        &self,
+        target: Target,
+        value: i64,
+        max_steps: u64,
+    ) -> Result<Option<Vec<i64>>, SymbolicError> {
+        self.walk(max_steps, |path| {
+            if path.halt != HaltStatus::Terminated {
+                return None;
+            }
+
+            let expr = match target {
+                Target::Memory(address) => path.memory.get(&address).cloned(),
+                Target::Output(n) => path.outputs.get(n).cloned(),
+            };
+
+            let linear = expr.unwrap_or(Expr::Const(0)).linear()?;
+
+            // The target might not care about a symbol the path does
+            let mut constrained = BTreeSet::new();
+            for condition in &path.conditions {
+                condition.expr.symbols(&mut constrained);
+            }
+            let constrained = constrained.into_iter().collect::<Vec<usize>>();
+
+            let found = solve_linear(&linear, value, &self.symbols, &constrained)
+                .find(|values| path.conditions.iter().all(|x| x.holds(values)));
+
+            found
+        })
+    }
+
+    fn step(&mut self) -> Result<Step, Fault> {
+        let address = self.pointer;
+        let word = self.load(address)?;
+        let word = word
+            .constant()
+            .ok_or(SymbolicError::SymbolicOpcode { address })?;
+        let opcode = parse_opcode(address, word)?;
+        let overflow = IntcodeError::Overflow { address };
+
+        match opcode {
+            OpCode::Add(x, y, z) | OpCode::Multiply(x, y, z) => {
+                let (a, b) = (self.read(1, x)?, self.read(2, y)?);
+                let destination = self.destination(3, z)?;
+                let value = match opcode {
+                    OpCode::Add(..) => Expr::add(a, b),
+                    _ => Expr::multiply(a, b),
+                };
+
+                self.memory.insert(destination, value.ok_or(overflow)?);
+            }
+            OpCode::LessThan(x, y, z) | OpCode::Equals(x, y, z) => {
+                let (a, b) = (self.read(1, x)?, self.read(2, y)?);
+                let destination = self.destination(3, z)?;
+                let equals = matches!(opcode, OpCode::Equals(..));
+
+                self.memory.insert(destination, Expr::compare(a, b, equals));
+            }
+            OpCode::Input(x) => {
+                if self.input.is_empty() {
+                    return Ok(Step::Halt(HaltStatus::WaitingInput));
+                }
+
+                let destination = self.destination(1, x)?;
+                let input = self.input.pop_front().unwrap();
+                self.memory.insert(destination, input);
+            }
+            OpCode::Output(x) => {
+                let output = self.read(1, x)?;
+                self.outputs.push(output);
+            }
+            OpCode::JumpIfTrue(x, y) | OpCode::JumpIfFalse(x, y) => {
+                let condition = self.read(1, x)?;
+                let target = self
+                    .read(2, y)?
+                    .constant()
+                    .ok_or(SymbolicError::SymbolicAddress { address })?;
+                let jump_if = matches!(opcode, OpCode::JumpIfTrue(..));
+
+                if let Some(condition) = condition.constant() {
+                    self.pointer = if (condition != 0) == jump_if {
+                        target
+                    } else {
+                        self.parameter(3)?
+                    };
+                    return Ok(Step::Next);
+                }
+
+                // Both ways, one of them on a copy
+                let next = self.parameter(3)?;
+                let mut other = self.clone();
+
+                self.conditions.push(Condition {
+                    expr: condition.clone(),
+                    nonzero: jump_if,
+                });
+                self.pointer = target;
+
+                other.conditions.push(Condition {
+                    expr: condition,
+                    nonzero: !jump_if,
+                });
+                other.pointer = next;
+
+                return Ok(Step::Fork(Box::new(other)));
+            }
+            OpCode::AdjustRelBase(x) => {
+                let offset = self
+                    .read(1, x)?
+                    .constant()
+                    .ok_or(SymbolicError::SymbolicAddress { address })?;
+
+                self.relative_base = self.relative_base.checked_add(offset).ok_or(overflow)?;
+            }
+            OpCode::Stop => return Ok(Step::Halt(HaltStatus::Terminated)),
+            OpCode::Custom(_) | OpCode::Skip => {
+                unreachable!("parse_opcode only gives the standard set")
+            }
+        }
+
+        self.pointer = self.parameter(opcode.arity() as i64 + 1)?;
+
+        Ok(Step::Next)
+    }
+
+    fn load(&self, address: i64) -> Result<Expr, IntcodeError> {
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress { address });
+        }
+
+        Ok(self.memory.get(&address).cloned().unwrap_or(Expr::Const(0)))
+    }
+
+    // Where a position or relative parameter word points, if it's known
+    fn address(&self, word: Expr, mode: ParameterMode) -> Result<Option<i64>, IntcodeError> {
+        let word = match word.constant() {
+            Some(word) => word,
+            None => return Ok(None),
+        };
+
+        match mode {
+            ParameterMode::Relative => {
+                self.relative_base
+                    .checked_add(word)
+                    .map(Some)
+                    .ok_or(IntcodeError::Overflow {
+                        address: self.pointer,
+                    })
+            }
+            _ => Ok(Some(word)),
+        }
+    }
+
+    // Where word `n` of the current instruction is
+    fn parameter(&self, n: i64) -> Result<i64, IntcodeError> {
+        self.pointer.checked_add(n).ok_or(IntcodeError::Overflow {
+            address: self.pointer,
+        })
+    }
+
+    // The value of parameter `n` of the current instruction
+    fn read(&self, n: i64, mode: ParameterMode) -> Result<Expr, IntcodeError> {
+        let word = self.load(self.parameter(n)?)?;
+
+        if mode == ParameterMode::Immediate {
+            return Ok(word);
+        }
+
+        match self.address(word, mode)? {
+            Some(address) => self.load(address),
+            None => Ok(Expr::Opaque),
+        }
+    }
+
+    // Where parameter `n` of the current instruction writes to
+    fn destination(&self, n: i64, mode: ParameterMode) -> Result<i64, Fault> {
+        if mode == ParameterMode::Immediate {
+            return Err(IntcodeError::ImmediateWrite {
+                address: self.pointer,
+            }
+            .into());
+        }
+
+        let word = self.load(self.parameter(n)?)?;
+
+        match self.address(word, mode)? {
+            Some(address) if address < 0 => Err(IntcodeError::NegativeAddress { address }.into()),
+            Some(address) => Ok(address),
+            None => Err(SymbolicError::SymbolicAddress {
+                address: self.pointer,
+            }
+            .into()),
+        }
+    }
+}
+
+enum Step {
+    Next,
+    // Carry on, and also run this other path later
+    Fork(Box<SymbolicProgram>),
+    Halt(HaltStatus),
+}
+
+// A fault ends one path, a symbolic error gives up on everything
+enum Fault {
+    Intcode(IntcodeError),
+    Symbolic(SymbolicError),
+}
+
+impl From<IntcodeError> for Fault {
+    fn from(err: IntcodeError) -> Fault {
+        Fault::Intcode(err)
+    }
+}
+
+impl From<SymbolicError> for Fault {
+    fn from(err: SymbolicError) -> Fault {
+        Fault::Symbolic(err)
+    }
+}
+
+// Every way of picking values for `symbols` that makes `linear` equal
+// `target`. All but one of the symbols it uses are tried across their
+// whole range, and the last one is worked out, so keep ranges small.
+// Symbols in `free` are tried across their whole range too, even if
+// `linear` doesn't use them, so something else (like a path condition)
+// can pick between them. Anything else is left at the bottom of its
+// range.
+pub fn solve_linear<'a>(
+    linear: &'a Linear,
+    target: i64,
+    symbols: &'a [Symbol],
+    free: &[usize],
+) -> impl Iterator<Item = Vec<i64>> + 'a {
+    let used = linear
+        .coefficients
+        .iter()
+        .filter(|x| *x.1 != 0)
+        .map(|x| *x.0)
+        .collect::<Vec<usize>>();
+    let start = symbols
+        .iter()
+        .map(|x| *x.range.start())
+        .collect::<Vec<i64>>();
+
+    let (last, rest) = match used.split_last() {
+        Some((last, rest)) => (Some(*last), rest.to_vec()),
+        None => (None, vec![]),
+    };
+    let mut enumerated = rest.clone();
+    enumerated.extend(free.iter().filter(|x| !used.contains(x)));
+
+    let mut values = Some(start);
+
+    std::iter::from_fn(move || loop {
+        let mut current = values.clone()?;
+
+        // On to the next combination, like an odometer
+        values = next_values(&current, &enumerated, symbols);
+
+        let sum = rest.iter().try_fold(linear.constant, |sum, x| {
+            sum.checked_add(linear.coefficients[x].checked_mul(current[*x])?)
+        });
+        let remainder = match sum.and_then(|x| target.checked_sub(x)) {
+            Some(remainder) => remainder,
+            None => continue,
+        };
+
+        match last {
+            None if remainder == 0 => return Some(current),
+            None => (),
+            Some(last) => {
+                let coefficient = linear.coefficients[&last];
+
+                // i64::MIN / -1 doesn't fit
+                if let (Some(0), Some(quotient)) = (
+                    remainder.checked_rem(coefficient),
+                    remainder.checked_div(coefficient),
+                ) {
+                    if symbols[last].range.contains(&quotient) {
+                        current[last] = quotient;
+                        return Some(current);
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn next_values(values: &[i64], enumerated: &[usize], symbols: &[Symbol]) -> Option<Vec<i64>> {
+    let mut values = values.to_vec();
+
+    for x in enumerated {
+        if values[*x] < *symbols[*x].range.end() {
+            values[*x] += 1;
+            return Some(values);
+        }
+
+        values[*x] = *symbols[*x].range.start();
+    }
+
+    None
+}
+
+#[test]
+fn day2_test() {
+    use super::{run_program, Program};
+    use crate::util;
+
+    let image = util::comma_separated_to_vec("data/d2.txt");
+    let mut program = SymbolicProgram::new(&image);
+    program.symbolic_cell(1, "noun", 0..=99);
+    program.symbolic_cell(2, "verb", 0..=99);
+
+    let values = program
+        .solve(Target::Memory(0), 19_690_720, 10_000)
+        .unwrap()
+        .unwrap();
+
+    let mut image = image;
+    image[1] = values[0];
+    image[2] = values[1];
+
+    let mut program = Program::new(image);
+    run_program(&mut program).unwrap();
+
+    assert_eq!(19_690_720, program.state.get(0));
+}
+
+#[test]
+fn branch_test() {
+    // Outputs 10 * x if x < 5, otherwise x + 100
+    let program = super::asm::assemble(
+        "
+        IN [x]
+        LT [x], #5, [small]
+        JNZ [small], #times
+        ADD [x], #100, [x]
+        OUT [x]
+        HALT
+times:  MUL [x], #10, [x]
+        OUT [x]
+        HALT
+x:      .data 0
+small:  .data 0",
+    )
+    .unwrap();
+
+    let mut program = SymbolicProgram::new(&program);
+    program.symbolic_input("x", 0..=1000);
+
+    assert_eq!(2, program.explore(100).unwrap().len());
+    assert_eq!(
+        Some(vec![4]),
+        program.solve(Target::Output(0), 40, 100).unwrap()
+    );
+    assert_eq!(
+        Some(vec![7]),
+        program.solve(Target::Output(0), 107, 100).unwrap()
+    );
+    // 10 * 6 would be 60, but 6 doesn't take that branch
+    assert_eq!(None, program.solve(Target::Output(0), 60, 100).unwrap());
+    assert_eq!(
+        Err(SymbolicError::StepLimit),
+        program.explore(3).map(|x| x.len())
+    );
+
+    // The output doesn't depend on x at all, but whether it happens
+    // does, so x can't just be left at 0
+    let program = super::asm::assemble(
+        "
+        IN [x]
+        LT #500, [x], [big]
+        JZ [big], #no
+        OUT #7
+no:     HALT
+x:      .data 0
+big:    .data 0",
+    )
+    .unwrap();
+
+    let mut program = SymbolicProgram::new(&program);
+    program.symbolic_input("x", 0..=1000);
+
+    assert_eq!(2, program.explore(100).unwrap().len());
+    assert_eq!(
+        Some(vec![501]),
+        program.solve(Target::Output(0), 7, 100).unwrap()
+    );
+
+    // The small side finishes first, so the loop on the other side
+    // never gets looked at
+    let program = super::asm::assemble(
+        "
+        IN [x]
+        LT [x], #5, [small]
+        JNZ [small], #done
+forever: JZ #0, #forever
+done:   OUT [x]
+        HALT
+x:      .data 0
+small:  .data 0",
+    )
+    .unwrap();
+
+    let mut program = SymbolicProgram::new(&program);
+    program.symbolic_input("x", 0..=1000);
+
+    assert_eq!(
+        Some(vec![3]),
+        program.solve(Target::Output(0), 3, 100).unwrap()
+    );
+    assert_eq!(
+        Err(SymbolicError::StepLimit),
+        program.solve(Target::Output(0), 30, 100)
+    );
+}
+
+#[test]
+fn solve_linear_test() {
+    let symbols = vec![Symbol {
+        name: "x".to_owned(),
+        range: -10..=10,
+    }];
+
+    // 3 - 2x
+    let linear = Linear {
+        constant: 3,
+        coefficients: vec![(0, -2)].into_iter().collect(),
+    };
+    assert_eq!(
+        vec![vec![-2]],
+        solve_linear(&linear, 7, &symbols, &[]).collect::<Vec<Vec<i64>>>()
+    );
+    assert_eq!(0, solve_linear(&linear, 8, &symbols, &[]).count());
+
+    // -x, where the target is too far away to divide back
+    let linear = Linear {
+        constant: 0,
+        coefficients: vec![(0, -1)].into_iter().collect(),
+    };
+    assert_eq!(0, solve_linear(&linear, i64::MIN, &symbols, &[]).count());
+}
@@ -0,0 +1,518 @@
+// Execution traces: one line per instruction, written as the program
+// runs, so two runs can be compared afterwards with `diff`.
+//
+//     intcode-trace 1
+//     0 IN 11 input 5 write 11 0 5
+//     2 MUL 5,3,11 write 11 5 15
+//     6 OUT 15 output 15
+//     8 ARB 2 rb 0 2
+//     10 HALT - halt terminated
+//
+// Each line is the address, the mnemonic, and the operands with their
+// modes applied (a write operand is the address written to), then
+// whichever of the input, write (address, old, new), output, relative
+// base (before, after) and halt status the instruction had, in that
+// order.
+//
+// An instruction that faults gets a last line with no operands and the
+// fault as its halt status, like `4 ADD - halt faulted overflow 4`. If
+// it couldn't even be decoded it's named `.data`.
+
+use super::disasm;
+use super::snapshot::{self, SnapshotError};
+use super::{HaltStatus, IntcodeError, MemoryWrite, Observer, OpCode, Program, StepResult};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub address: i64,
+    pub mnemonic: String,
+    pub operands: Vec<i64>,
+    pub write: Option<MemoryWrite>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+    pub relative_base: Option<(i64, i64)>,
+    pub halt: Option<HaltStatus>,
+}
+
+impl Record {
+    pub fn new(step: &StepResult) -> Record {
+        Record {
+            address: step.address,
+            mnemonic: disasm::mnemonic(step.opcode).to_owned(),
+            operands: step.operands().to_vec(),
+            write: step.write,
+            input: step.input,
+            output: step.output,
+            relative_base: step.relative_base,
+            halt: step.halt,
+        }
+    }
+
+    pub fn parse(line: usize, text: &str) -> Result<Record, TraceError> {
+        let mut words = text.split_whitespace();
+        let mut next = |what| {
+            words
+                .next()
+                .ok_or_else(|| parse_error(line, &format!("missing {}", what)))
+        };
+
+        let mut record = Record {
+            address: parse_number(line, next("address")?)?,
+            mnemonic: next("mnemonic")?.to_owned(),
+            operands: match next("operands")? {
+                "-" => vec![],
+                x => x
+                    .split(',')
+                    .map(|x| parse_number(line, x))
+                    .collect::<Result<Vec<i64>, TraceError>>()?,
+            },
+            write: None,
+            input: None,
+            output: None,
+            relative_base: None,
+            halt: None,
+        };
+
+        while let Ok(field) = next("field") {
+            match field {
+                "write" => {
+                    record.write = Some(MemoryWrite {
+                        address: parse_number(line, next("write address")?)?,
+                        old: parse_number(line, next("old value")?)?,
+                        new: parse_number(line, next("new value")?)?,
+                    })
+                }
+                "input" => record.input = Some(parse_number(line, next("input")?)?),
+                "output" => record.output = Some(parse_number(line, next("output")?)?),
+                "rb" => {
+                    record.relative_base = Some((
+                        parse_number(line, next("relative base")?)?,
+                        parse_number(line, next("relative base")?)?,
+                    ))
+                }
+                "halt" => {
+                    // The rest of the line, which is always last
+                    let status = std::iter::from_fn(|| next("").ok())
+                        .collect::<Vec<&str>>()
+                        .join(" ");
+
+                    record.halt = snapshot::parse_halt_status(line, &status)?;
+                }
+                x => return Err(parse_error(line, &format!("unknown field: {}", x))),
+            }
+        }
+
+        Ok(record)
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operands = match self.operands.is_empty() {
+            true => "-".to_owned(),
+            false => self
+                .operands
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+        };
+
+        write!(f, "{} {} {}", self.address, self.mnemonic, operands)?;
+
+        if let Some(input) = self.input {
+            write!(f, " input {}", input)?;
+        }
+        if let Some(write) = self.write {
+            write!(f, " write {} {} {}", write.address, write.old, write.new)?;
+        }
+        if let Some(output) = self.output {
+            write!(f, " output {}", output)?;
+        }
+        if let Some((before, after)) = self.relative_base {
+            write!(f, " rb {} {}", before, after)?;
+        }
+        if self.halt.is_some() {
+            write!(f, " halt {}", snapshot::halt_status_to_string(self.halt))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    UnsupportedVersion(i64),
+    // 1-based line in the trace
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(err) => write!(f, "couldn't read trace: {}", err),
+            TraceError::UnsupportedVersion(version) => write!(
+                f,
+                "trace version {} isn't supported (expected {})",
+                version, VERSION
+            ),
+            TraceError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(err: io::Error) -> TraceError {
+        TraceError::Io(err)
+    }
+}
+
+// Halt statuses are parsed the same way snapshots do it
+impl From<SnapshotError> for TraceError {
+    fn from(err: SnapshotError) -> TraceError {
+        match err {
+            SnapshotError::Io(err) => TraceError::Io(err),
+            SnapshotError::UnsupportedVersion(x) => TraceError::UnsupportedVersion(x),
+            SnapshotError::Parse { line, message } => TraceError::Parse { line, message },
+            // Only comes from saving, which traces never do
+            SnapshotError::Unsaveable(reason) => TraceError::Parse {
+                line: 0,
+                message: format!("can't save {}", reason),
+            },
+        }
+    }
+}
+
+// Writes a line for every step it sees. Observers can't fail, so the
+// first write error is kept and handed back by `finish`, and nothing
+// more is written after it.
+pub struct Recorder<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+    steps: usize,
+    // The instruction that's running, so a fault can say what it was
+    current: Option<(i64, OpCode)>,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W) -> io::Result<Recorder<W>> {
+        writeln!(out, "intcode-trace {}", VERSION)?;
+
+        Ok(Recorder {
+            out,
+            error: None,
+            steps: 0,
+            current: None,
+        })
+    }
+
+    // Instructions that ran and were recorded. A fault's line isn't
+    // one of them.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.out.flush().map(|_| self.out),
+        }
+    }
+
+    // Returns whether it was written
+    fn write(&mut self, record: Record) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+
+        match writeln!(self.out, "{}", record) {
+            Ok(()) => true,
+            Err(err) => {
+                self.error = Some(err);
+                false
+            }
+        }
+    }
+}
+
+impl<W: Write> Observer for Recorder<W> {
+    fn before_step(&mut self, _program: &Program, address: i64, opcode: OpCode) {
+        self.current = Some((address, opcode));
+    }
+
+    fn after_step(&mut self, _program: &Program, step: &StepResult) {
+        self.current = None;
+        if self.write(Record::new(step)) {
+            self.steps += 1;
+        }
+    }
+
+    fn fault(&mut self, program: &Program, error: IntcodeError) {
+        let address = program.pointer;
+        // Decoding faults come before `before_step`
+        let mnemonic = match self.current.take() {
+            Some((x, opcode)) if x == address => disasm::mnemonic(opcode),
+            _ => ".data",
+        };
+
+        // Not a step, as it never finished
+        self.write(Record {
+            address,
+            mnemonic: mnemonic.to_owned(),
+            operands: vec![],
+            write: None,
+            input: None,
+            output: None,
+            relative_base: None,
+            halt: Some(HaltStatus::Faulted(error)),
+        });
+    }
+}
+
+// Reads records back one at a time, so long traces don't have to fit in
+// memory
+pub struct Reader<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(input: R) -> Result<Reader<R>, TraceError> {
+        let mut lines = input.lines();
+
+        match lines.next().transpose()? {
+            Some(header) => match header.trim().strip_prefix("intcode-trace ") {
+                Some(version) => match parse_number(1, version)? {
+                    x if x == i64::from(VERSION) => (),
+                    x => return Err(TraceError::UnsupportedVersion(x)),
+                },
+                None => return Err(parse_error(1, "not an Intcode trace")),
+            },
+            None => return Err(parse_error(1, "empty trace")),
+        }
+
+        Ok(Reader { lines, line: 1 })
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Record, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line += 1;
+
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            if !text.trim().is_empty() {
+                return Some(Record::parse(self.line, &text));
+            }
+        }
+    }
+}
+
+// The first step where two traces disagree. One trace may have ended
+// early, in which case its side is None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    // 0-based, and also how many steps matched before it
+    pub step: usize,
+    pub left: Option<Record>,
+    pub right: Option<Record>,
+    // The last step both agreed on
+    pub previous: Option<Record>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |x: &Option<Record>| match x {
+            Some(record) => record.to_string(),
+            None => "(end of trace)".to_owned(),
+        };
+
+        writeln!(f, "traces diverge at step {}", self.step)?;
+        if let Some(previous) = &self.previous {
+            writeln!(f, "  after: {}", previous)?;
+        }
+        writeln!(f, "  left:  {}", side(&self.left))?;
+        write!(f, "  right: {}", side(&self.right))
+    }
+}
+
+// Lines the traces up step by step and finds where they first differ,
+// or None if they're the same all the way through
+pub fn diff(
+    left: impl IntoIterator<Item = Result<Record, TraceError>>,
+    right: impl IntoIterator<Item = Result<Record, TraceError>>,
+) -> Result<Option<Divergence>, TraceError> {
+    let (mut left, mut right) = (left.into_iter(), right.into_iter());
+    let mut previous = None;
+
+    for step in 0.. {
+        let (x, y) = (left.next().transpose()?, right.next().transpose()?);
+
+        if x.is_none() && y.is_none() {
+            return Ok(None);
+        }
+
+        if x != y {
+            return Ok(Some(Divergence {
+                step,
+                left: x,
+                right: y,
+                previous,
+            }));
+        }
+
+        previous = x;
+    }
+
+    unreachable!()
+}
+
+fn parse_error(line: usize, message: &str) -> TraceError {
+    TraceError::Parse {
+        line,
+        message: message.to_owned(),
+    }
+}
+
+fn parse_number(line: usize, text: &str) -> Result<i64, TraceError> {
+    text.trim()
+        .parse::<i64>()
+        .map_err(|_| parse_error(line, &format!("bad number: {}", text)))
+}
+
+#[cfg(test)]
+fn record(image: Vec<i64>, inputs: &[i64]) -> Vec<u8> {
+    let mut program = Program::new(image);
+    let mut recorder = Recorder::new(Vec::new()).unwrap();
+
+    for input in inputs {
+        super::push_input(&mut program, *input);
+    }
+    super::run_program_with(&mut program, &mut recorder).unwrap();
+
+    recorder.finish().unwrap()
+}
+
+#[test]
+fn round_trip_test() {
+    let trace = record(vec![3, 11, 1002, 11, 3, 11, 4, 11, 109, 2, 99, 0], &[5]);
+    let text = String::from_utf8(trace.clone()).unwrap();
+
+    assert_eq!(
+        "intcode-trace 1
+0 IN 11 input 5 write 11 0 5
+2 MUL 5,3,11 write 11 5 15
+6 OUT 15 output 15
+8 ARB 2 rb 0 2
+10 HALT - halt terminated
+",
+        text
+    );
+
+    let records = Reader::new(&trace[..])
+        .unwrap()
+        .collect::<Result<Vec<Record>, TraceError>>()
+        .unwrap();
+    assert_eq!(
+        text.lines().skip(1).collect::<Vec<&str>>(),
+        records
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+    );
+
+    assert!(matches!(
+        Reader::new(&b"intcode-trace 2\n"[..]),
+        Err(TraceError::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        Reader::new(&b"intcode-trace 1\n0 ADD 1,2,3 write 3\n"[..])
+            .unwrap()
+            .next(),
+        Some(Err(TraceError::Parse { line: 2, .. }))
+    ));
+}
+
+#[test]
+fn diff_test() {
+    // Adds one to its input, and outputs that unless it's 8
+    let image = vec![
+        3, 16, 1001, 16, 1, 16, 1008, 16, 8, 17, 1005, 17, 15, 4, 16, 99, 0, 0,
+    ];
+    let read = |trace: &Vec<u8>| Reader::new(&trace[..]).unwrap().collect::<Vec<_>>();
+
+    let seven = record(image.clone(), &[7]);
+    let three = record(image, &[3]);
+
+    assert_eq!(None, diff(read(&three), read(&three)).unwrap());
+
+    // Diverges straight away on the input...
+    let divergence = diff(read(&seven), read(&three)).unwrap().unwrap();
+    assert_eq!(0, divergence.step);
+    assert_eq!(None, divergence.previous);
+
+    // ...and when one trace stops early, where it stops
+    let divergence = diff(read(&three), read(&three).into_iter().take(2))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        "traces diverge at step 2
+  after: 2 ADD 3,1,16 write 16 3 4
+  left:  6 EQ 4,8,17 write 17 0 0
+  right: (end of trace)",
+        divergence.to_string()
+    );
+}
+
+#[test]
+fn fault_test() {
+    let trace = |image: Vec<i64>| {
+        let mut program = Program::new(image);
+        let mut recorder = Recorder::new(Vec::new()).unwrap();
+
+        super::run_program_with(&mut program, &mut recorder).unwrap_err();
+        assert_eq!(1, recorder.steps());
+
+        String::from_utf8(recorder.finish().unwrap()).unwrap()
+    };
+
+    // Overflows on the second ADD
+    assert_eq!(
+        "intcode-trace 1
+0 ADD 9223372036854775807,0,9 write 9 0 9223372036854775807
+4 ADD - halt faulted overflow 4
+",
+        trace(vec![1101, i64::MAX, 0, 9, 1001, 9, 1, 9, 99, 0])
+    );
+
+    // Can't decode the second instruction at all
+    let text = trace(vec![104, 1, 42]);
+    assert!(text.ends_with("2 .data - halt faulted unknown-opcode 2,42\n"));
+
+    // And the fault reads back like any other record
+    let records = Reader::new(text.as_bytes())
+        .unwrap()
+        .collect::<Result<Vec<Record>, TraceError>>()
+        .unwrap();
+    assert_eq!(
+        Some(HaltStatus::Faulted(IntcodeError::UnknownOpcode {
+            address: 2,
+            opcode: 42
+        })),
+        records[1].halt
+    );
+}
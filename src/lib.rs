@@ -0,0 +1,20 @@
+// Listing a day here is all it takes to add it: this declares its
+// module and has `Registry::all` call its `register`
+macro_rules! days {
+    ($($day:ident),* $(,)?) => {
+        $(pub mod $day;)*
+
+        pub(crate) fn register_days(registry: &mut solution::Registry) {
+            $($day::register(registry);)*
+        }
+    };
+}
+
+days! {
+    day1, day2, day3, day4, day5, day6, day7, day8, day9, day10, day11, day12, day13, day14,
+    day15, day16, day17,
+}
+
+pub mod intcode;
+pub mod solution;
+pub mod util;
@@ -1,282 +1,279 @@
-mod day1;
-mod day10;
-mod day11;
-mod day12;
-mod day13;
-mod day14;
-mod day15;
-mod day16;
-mod day17;
-mod day2;
-mod day3;
-mod day4;
-mod day5;
-mod day6;
-mod day7;
-mod day8;
-mod day9;
-mod intcode;
-mod util;
-
+use aoc2019::intcode::analysis::Analysis;
+use aoc2019::intcode::debugger::Debugger;
+use aoc2019::intcode::disasm;
+use aoc2019::intcode::memory::{FlatMemory, Memory, SparseMemory};
+use aoc2019::intcode::profiler::Profiler;
+use aoc2019::intcode::trace::{self, Recorder};
+use aoc2019::intcode::{self, snapshot, Program};
+use aoc2019::solution::{Registry, Solution};
+use aoc2019::util;
 use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
 use std::time::Instant;
 
 fn main() {
     env_logger::init();
+
+    let registry = Registry::all();
+
     match env::args().nth(1) {
-        None => run_all(),
+        None => run(registry.solutions()),
         Some(exercise) => match exercise.as_ref() {
-            "d1p1" => run_one(day1::part1),
-            "d1p2" => run_one(day1::part2),
-            "d2p1" => run_one(day2::part1),
-            "d2p2" => run_one(day2::part2),
-            "d3p1" => run_one(day3::part1),
-            "d3p2" => run_one(day3::part2),
-            "d4p1" => run_one(day4::part1),
-            "d4p2" => run_one(day4::part2),
-            "d5p1" => run_one(day5::part1),
-            "d5p2" => run_one(day5::part2),
-            "d6p1" => run_one(day6::part1),
-            "d6p2" => run_one(day6::part2),
-            "d7p1" => run_one(day7::part1),
-            "d7p2" => run_one(day7::part2),
-            "d8p1" => run_one(day8::part1),
-            "d8p2" => run_one(day8::part2),
-            "d9p1" => run_one(day9::part1),
-            "d9p2" => run_one(day9::part2),
-            "d10p1" => run_one(day10::part1),
-            "d10p2" => run_one(day10::part2),
-            "d11p1" => run_one(day11::part1),
-            "d11p2" => run_one(day11::part2),
-            "d12p1" => run_one(day12::part1),
-            "d12p2" => run_one(day12::part2),
-            "d13p1" => run_one(day13::part1),
-            "d13p2" => run_one(day13::part2),
-            "d14p1" => run_one(day14::part1),
-            "d14p2" => run_one(day14::part2),
-            "d15p1" => run_one(day15::part1),
-            "d15p2" => run_one(day15::part2),
-            "d16p1" => run_one(day16::part1),
-            "d16p2" => run_one(day16::part2),
-            "d17p1" => run_one(day17::part1),
-            "d17p2" => run_one(day17::part2),
-            _ => panic!("unknown exercise: {}", exercise),
+            "ascii" => ascii(Program::new(util::comma_separated_to_vec(&file_argument()))),
+            "bench-memory" => bench_memory(),
+            "cfg" => control_flow_graph(&file_argument()),
+            "debug" => debug(Program::new(util::comma_separated_to_vec(&file_argument()))),
+            "disasm" => disassemble(&file_argument()),
+            "profile" => profile(&file_argument()),
+            "snapshot" => run_and_snapshot(
+                Program::new(util::comma_separated_to_vec(&file_argument())),
+                &output_argument(),
+            ),
+            "resume" => match snapshot::load_from_file(&file_argument()) {
+                Ok(program) => run_and_snapshot(program, &output_argument()),
+                Err(err) => println!("Couldn't load snapshot: {}", err),
+            },
+            "trace" => run_and_trace(
+                Program::new(util::comma_separated_to_vec(&file_argument())),
+                &output_argument(),
+            ),
+            "trace-diff" => trace_diff(&file_argument(), &output_argument()),
+            "list" => list(registry.filter(&filter_argument())),
+            "run" => run(registry.filter(&filter_argument())),
+            name => match registry.filter(name).count() {
+                0 => panic!("unknown exercise: {}", name),
+                _ => run(registry.filter(name)),
+            },
         },
     }
 }
 
-fn run_all() {
+fn list<'a>(solutions: impl Iterator<Item = &'a dyn Solution>) {
+    for solution in solutions {
+        match solution.expected() {
+            Some(answer) => println!("{} (expects {})", solution.name(), answer),
+            None => println!("{}", solution.name()),
+        }
+    }
+}
+
+// Runs each solution on its puzzle input, checking the answers we
+// know. Panics at the end if any were wrong.
+fn run<'a>(solutions: impl Iterator<Item = &'a dyn Solution>) {
     let start = Instant::now();
+    let mut failed = Vec::new();
+
+    for solution in solutions {
+        let input = input(solution.day());
+        let now = Instant::now();
+        let answer = match solution.solve(&input) {
+            Ok(answer) => answer,
+            Err(e) => {
+                println!("{} failed! {}", solution.name(), e);
+                failed.push(solution.name());
+                continue;
+            }
+        };
+
+        println!(
+            "{}: {} (elapsed time: {:?})",
+            solution.name(),
+            answer,
+            Instant::elapsed(&now)
+        );
+
+        if let Some(expected) = solution.expected() {
+            if *expected != answer {
+                println!("{} failed! Expected {}", solution.name(), expected);
+                failed.push(solution.name());
+            }
+        }
+    }
 
-    assert_eq!(
-        3_442_987,
-        run_one_and_return("day1::part1", day1::part1),
-        "day1::part1 failed!"
-    );
+    println!("Total elapsed time: {:?}", Instant::elapsed(&start));
 
-    assert_eq!(
-        5_161_601,
-        run_one_and_return("day1::part2", day1::part2),
-        "day1::part2 failed!"
-    );
+    assert!(failed.is_empty(), "failed: {}", failed.join(", "));
+}
 
-    assert_eq!(
-        3_306_701,
-        run_one_and_return("day2::part1", day2::part1),
-        "day2::part1 failed!"
-    );
+// The puzzle input for `day`
+fn input(day: u32) -> String {
+    util::file_as_string(&format!("data/d{}.txt", day))
+}
 
-    assert_eq!(
-        7_621,
-        run_one_and_return("day2::part2", day2::part2),
-        "day2::part2 failed!"
-    );
+fn file_argument() -> String {
+    env::args().nth(2).expect("missing file argument")
+}
 
-    assert_eq!(
-        651,
-        run_one_and_return("day3::part1", day3::part1),
-        "day3::part1 failed!"
-    );
+// Which solutions to list or run, everything if missing
+fn filter_argument() -> String {
+    env::args().nth(2).unwrap_or_default()
+}
 
-    assert_eq!(
-        7_534,
-        run_one_and_return("day3::part2", day3::part2),
-        "day3::part2 failed!"
-    );
+fn output_argument() -> String {
+    env::args().nth(3).expect("missing output file argument")
+}
 
-    assert_eq!(
-        2_150,
-        run_one_and_return("day4::part1", day4::part1),
-        "day4::part1 failed!"
-    );
+// Anything from argument `first` on is fed to the program as input
+fn input_arguments(first: usize) -> Vec<i64> {
+    env::args()
+        .skip(first)
+        .map(|x| x.parse::<i64>().expect("inputs must be integers"))
+        .collect()
+}
 
-    assert_eq!(
-        1_462,
-        run_one_and_return("day4::part2", day4::part2),
-        "day4::part2 failed!"
-    );
+fn ascii(mut program: Program) {
+    let stdin = io::stdin();
 
-    assert_eq!(
-        9_938_601,
-        run_one_and_return("day5::part1", day5::part1),
-        "day5::part1 failed!"
-    );
+    intcode::ascii::interact(&mut program, stdin.lock(), io::stdout()).unwrap();
+}
 
-    assert_eq!(
-        4_283_952,
-        run_one_and_return("day5::part2", day5::part2),
-        "day5::part2 failed!"
-    );
+fn debug(program: Program) {
+    let stdin = io::stdin();
 
-    assert_eq!(
-        621_125,
-        run_one_and_return("day6::part1", day6::part1),
-        "day6::part1 failed!"
-    );
+    Debugger::new(program)
+        .run(stdin.lock(), io::stdout())
+        .unwrap();
+}
 
-    assert_eq!(
-        550,
-        run_one_and_return("day6::part2", day6::part2),
-        "day6::part2 failed!"
+fn disassemble(filepath: &str) {
+    println!(
+        "{}",
+        disasm::listing(&util::comma_separated_to_vec(filepath))
     );
+}
 
-    assert_eq!(
-        21_000,
-        run_one_and_return("day7::part1", day7::part1),
-        "day7::part1 failed!"
-    );
+// Graphviz on stdout, a summary of what the analysis found on stderr
+fn control_flow_graph(filepath: &str) {
+    let analysis = Analysis::new(&util::comma_separated_to_vec(filepath));
 
-    assert_eq!(
-        61_379_886,
-        run_one_and_return("day7::part2", day7::part2),
-        "day7::part2 failed!"
-    );
+    print!("{}", analysis.to_dot());
 
-    assert_eq!(
-        1_792,
-        run_one_and_return("day8::part1", day8::part1),
-        "day8::part1 failed!"
+    eprintln!(
+        "{} blocks, {} edges",
+        analysis.blocks.len(),
+        analysis.edges.len()
     );
+    for x in &analysis.self_modifications {
+        eprintln!(
+            "{} writes to {}, part of the instruction at {}",
+            x.address, x.target, x.instruction
+        );
+    }
+    for x in analysis.subroutines.values() {
+        eprintln!(
+            "subroutine at {}: {} blocks, called from {:?}",
+            x.entry,
+            x.blocks.len(),
+            x.callers
+        );
+    }
+}
 
-    assert_eq!(
-        42,
-        run_one_and_return("day8::part2", day8::part2),
-        "day8::part2 failed!"
-    );
+fn profile(filepath: &str) {
+    let image = util::comma_separated_to_vec(filepath);
+    let mut program = Program::new(image.clone());
+    let mut profiler = Profiler::new();
 
-    assert_eq!(
-        2_752_191_671,
-        run_one_and_return("day9::part1", day9::part1),
-        "day9::part1 failed!"
-    );
+    for input in input_arguments(3) {
+        intcode::push_input(&mut program, input);
+    }
 
-    assert_eq!(
-        87_571,
-        run_one_and_return("day9::part2", day9::part2),
-        "day9::part2 failed!"
-    );
+    if let Err(err) = intcode::run_program_with(&mut program, &mut profiler) {
+        println!("Program faulted: {}", err);
+    }
 
-    assert_eq!(
-        260,
-        run_one_and_return("day10::part1", day10::part1),
-        "day10::part1 failed!"
-    );
+    while let Some(output) = intcode::get_next_output(&mut program) {
+        println!("{}", output);
+    }
 
-    assert_eq!(
-        608,
-        run_one_and_return("day10::part2", day10::part2),
-        "day10::part2 failed!"
-    );
+    println!("Halt status: {:?}", program.halt_status);
+    println!("{}", profiler.report(&image));
+}
 
-    assert_eq!(
-        2_160,
-        run_one_and_return("day11::part1", day11::part1),
-        "day11::part1 failed!"
-    );
+// Runs day 9's BOOST program in sensor boost mode on each memory
+// backend, keeping the best of a few runs
+fn bench_memory() {
+    let image = util::comma_separated_to_vec("data/d9.txt");
 
-    assert_eq!(
-        42,
-        run_one_and_return("day11::part2", day11::part2),
-        "day11::part2 failed!"
-    );
+    let backends: Vec<(&str, Box<dyn Memory>)> = vec![
+        ("flat", Box::new(FlatMemory::from(image.clone()))),
+        ("sparse", Box::new(SparseMemory::from(image))),
+    ];
 
-    assert_eq!(
-        9_139,
-        run_one_and_return("day12::part1", day12::part1),
-        "day12::part1 failed!"
-    );
-
-    assert_eq!(
-        420_788_524_631_496,
-        run_one_and_return("day12::part2", day12::part2),
-        "day12::part2 failed!"
-    );
+    for (name, memory) in backends {
+        let best = (0..5)
+            .map(|_| {
+                let mut program = Program::with_memory(memory.clone());
+                intcode::push_input(&mut program, 2);
 
-    assert_eq!(
-        200,
-        run_one_and_return("day13::part1", day13::part1),
-        "day13::part1 failed!"
-    );
+                let now = Instant::now();
+                intcode::run_program(&mut program).unwrap();
+                let elapsed = Instant::elapsed(&now);
 
-    assert_eq!(
-        9_803,
-        run_one_and_return("day13::part2", day13::part2),
-        "day13::part2 failed!"
-    );
+                assert_eq!(Some(87_571), intcode::get_next_output(&mut program));
 
-    assert_eq!(
-        532_506,
-        run_one_and_return("day14::part1", day14::part1),
-        "day14::part1 failed!"
-    );
+                elapsed
+            })
+            .min()
+            .unwrap();
 
-    assert_eq!(
-        2_595_245,
-        run_one_and_return("day14::part2", day14::part2),
-        "day14::part2 failed!"
-    );
+        println!("Best time for {} memory: {:?}", name, best);
+    }
+}
 
-    assert_eq!(
-        224,
-        run_one_and_return("day15::part1", day15::part1),
-        "day15::part1 failed!"
-    );
+// Runs until the program stops or wants more input, prints what it
+// said, and saves the machine so it can be resumed later
+fn run_and_snapshot(mut program: Program, snapshot_path: &str) {
+    for input in input_arguments(4) {
+        intcode::push_input(&mut program, input);
+    }
 
-    assert_eq!(
-        284,
-        run_one_and_return("day15::part2", day15::part2),
-        "day15::part2 failed!"
-    );
+    if let Err(err) = intcode::run_program(&mut program) {
+        println!("Program faulted: {}", err);
+    }
 
-    assert_eq!(
-        27_229_269,
-        run_one_and_return("day16::part1", day16::part1),
-        "day16::part1 failed!"
-    );
+    while let Some(output) = intcode::get_next_output(&mut program) {
+        println!("{}", output);
+    }
 
-    assert_eq!(
-        26_857_164,
-        run_one_and_return("day16::part2", day16::part2),
-        "day16::part2 failed!"
-    );
+    println!("Halt status: {:?}", program.halt_status);
 
-    println!("Total elapsed time: {:?}", Instant::elapsed(&start));
+    if let Err(err) = snapshot::save_to_file(&program, snapshot_path) {
+        println!("Couldn't save snapshot: {}", err);
+    }
 }
 
-fn run_one(exercise: fn() -> i64) {
-    let now = Instant::now();
+fn run_and_trace(mut program: Program, trace_path: &str) {
+    let mut recorder = Recorder::new(BufWriter::new(File::create(trace_path).unwrap())).unwrap();
 
-    println!("{}", exercise());
+    for input in input_arguments(4) {
+        intcode::push_input(&mut program, input);
+    }
 
-    println!("Elapsed time: {:?}", Instant::elapsed(&now));
-}
+    if let Err(err) = intcode::run_program_with(&mut program, &mut recorder) {
+        println!("Program faulted: {}", err);
+    }
 
-fn run_one_and_return(name: &str, exercise: fn() -> i64) -> i64 {
-    let now = Instant::now();
+    while let Some(output) = intcode::get_next_output(&mut program) {
+        println!("{}", output);
+    }
+
+    println!("Halt status: {:?}", program.halt_status);
+    println!("Traced {} steps", recorder.steps());
 
-    let result = exercise();
+    recorder.finish().unwrap();
+}
 
-    println!("Elapsed time for {}: {:?}", name, Instant::elapsed(&now));
+fn trace_diff(left_path: &str, right_path: &str) {
+    let read = |path| {
+        trace::Reader::new(BufReader::new(File::open(path).unwrap()))
+            .unwrap_or_else(|err| panic!("{}: {}", path, err))
+    };
 
-    result
+    match trace::diff(read(left_path), read(right_path)) {
+        Ok(None) => println!("Traces match"),
+        Ok(Some(divergence)) => println!("{}", divergence),
+        Err(err) => println!("Couldn't compare traces: {}", err),
+    }
 }
@@ -0,0 +1,172 @@
+// Every puzzle part, behind one interface, so the CLI can list and run
+// them without knowing about each day. A day adds itself with a
+// `register` function, and gets listed in the `days!` list in lib.rs:
+//
+//     pub fn register(registry: &mut Registry) {
+//         registry.add(Part::new(1, 1, parse, part1).expecting(3_442_987));
+//     }
+
+use crate::util::ParseError;
+use std::borrow::Borrow;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Number(i64),
+    Text(String),
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Answer::Number(x) => write!(f, "{}", x),
+            Answer::Text(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl From<i64> for Answer {
+    fn from(x: i64) -> Answer {
+        Answer::Number(x)
+    }
+}
+
+impl From<String> for Answer {
+    fn from(x: String) -> Answer {
+        Answer::Text(x)
+    }
+}
+
+pub trait Solution {
+    fn day(&self) -> u32;
+
+    fn part(&self) -> u32;
+
+    // What it's called on the command line
+    fn name(&self) -> String {
+        format!("d{}p{}", self.day(), self.part())
+    }
+
+    // The answer for our puzzle input, if we know it
+    fn expected(&self) -> Option<&Answer> {
+        None
+    }
+
+    // Takes the raw puzzle input
+    fn solve(&self, input: &str) -> Result<Answer, ParseError>;
+}
+
+// A solution made from a day's `parse` and one of its parts. The part
+// can take a borrowed form of what `parse` gives, like a slice of a
+// Vec.
+pub struct Part<P, I: ?Sized, A> {
+    day: u32,
+    part: u32,
+    parse: fn(&str) -> Result<P, ParseError>,
+    solve: fn(&I) -> A,
+    expected: Option<Answer>,
+}
+
+impl<P, I: ?Sized, A> Part<P, I, A> {
+    pub fn new(
+        day: u32,
+        part: u32,
+        parse: fn(&str) -> Result<P, ParseError>,
+        solve: fn(&I) -> A,
+    ) -> Part<P, I, A> {
+        Part {
+            day,
+            part,
+            parse,
+            solve,
+            expected: None,
+        }
+    }
+
+    pub fn expecting(mut self, answer: impl Into<Answer>) -> Part<P, I, A> {
+        self.expected = Some(answer.into());
+        self
+    }
+}
+
+impl<P: Borrow<I>, I: ?Sized, A: Into<Answer>> Solution for Part<P, I, A> {
+    fn day(&self) -> u32 {
+        self.day
+    }
+
+    fn part(&self) -> u32 {
+        self.part
+    }
+
+    fn expected(&self) -> Option<&Answer> {
+        self.expected.as_ref()
+    }
+
+    fn solve(&self, input: &str) -> Result<Answer, ParseError> {
+        let parsed = (self.parse)(input)?;
+        Ok((self.solve)(parsed.borrow()).into())
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    solutions: Vec<Box<dyn Solution>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    // Every day's solutions, in order
+    pub fn all() -> Registry {
+        let mut registry = Registry::new();
+
+        crate::register_days(&mut registry);
+
+        registry.solutions.sort_by_key(|x| (x.day(), x.part()));
+        registry
+    }
+
+    pub fn add(&mut self, solution: impl Solution + 'static) {
+        self.solutions.push(Box::new(solution));
+    }
+
+    pub fn solutions(&self) -> impl Iterator<Item = &dyn Solution> {
+        self.solutions.iter().map(|x| x.as_ref())
+    }
+
+    pub fn get(&self, day: u32, part: u32) -> Option<&dyn Solution> {
+        self.solutions()
+            .find(|x| x.day() == day && x.part() == part)
+    }
+
+    // Everything matching `filter`: a name like "d13p2", a whole day
+    // like "d13", or "" for everything
+    pub fn filter<'a>(&'a self, filter: &'a str) -> impl Iterator<Item = &'a dyn Solution> {
+        self.solutions().filter(move |x| {
+            filter.is_empty() || filter == x.name() || filter == format!("d{}", x.day())
+        })
+    }
+}
+
+#[test]
+fn registry_test() {
+    let registry = Registry::all();
+
+    assert_eq!(34, registry.solutions().count());
+    assert_eq!(
+        vec!["d13p1", "d13p2"],
+        registry
+            .filter("d13")
+            .map(|x| x.name())
+            .collect::<Vec<String>>()
+    );
+    assert_eq!(1, registry.filter("d1p2").count());
+    assert_eq!(0, registry.filter("d18").count());
+
+    let day1 = registry.get(1, 2).unwrap();
+    assert_eq!(Ok(Answer::Number(2 + 966)), day1.solve("14\n1969\n"));
+    assert!(day1.solve("14\nlots\n").is_err());
+    assert_eq!(Some(&Answer::Number(5_161_601)), day1.expected());
+}
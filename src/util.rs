@@ -1,7 +1,10 @@
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{self, BufRead, BufReader};
 use std::path::Path;
+use std::str::FromStr;
 
 pub fn lines_from_path(filepath: &str) -> io::Lines<std::io::BufReader<std::fs::File>> {
     let file = File::open(Path::new(filepath)).unwrap();
@@ -10,21 +13,42 @@ pub fn lines_from_path(filepath: &str) -> io::Lines<std::io::BufReader<std::fs::
 }
 
 pub fn comma_separated_to_vec(filepath: &str) -> Vec<i64> {
-    let mut file = File::open(Path::new(filepath)).unwrap();
-
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-
-    contents
-        .split(',')
-        .map(|x| match x.trim().parse::<i64>() {
-            Ok(parsed) => parsed,
-            Err(err) => {
-                println!("Error parsing string: {} with err: {}", x, err);
-                0
-            }
-        })
-        .collect()
+    match parse_comma_separated(&file_as_string(filepath)) {
+        Ok(parsed) => parsed,
+        Err(e) => panic!("Error parsing {}: {}", filepath, e),
+    }
+}
+
+pub fn parse_comma_separated(input: &str) -> Result<Vec<i64>, ParseError> {
+    input.split(',').map(|x| parse_number(x.trim())).collect()
+}
+
+// Puzzle input that isn't shaped the way a day expects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bad input: {}", self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+pub fn parse_number<T: FromStr>(input: &str) -> Result<T, ParseError> {
+    input
+        .parse()
+        .map_err(|_| ParseError::new(format!("not a number: {:?}", input)))
 }
 
 pub fn file_as_string(filepath: &str) -> String {